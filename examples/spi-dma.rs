@@ -0,0 +1,54 @@
+//! Full-duplex SPI transfer using DMA.
+//!
+//! Connect MOSI (PA7) to MISO (PA6) to loop the data back.
+
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+use cortex_m_rt::entry;
+use stm32f4xx_hal as hal;
+
+use crate::hal::{
+    dma::StreamsTuple,
+    pac,
+    prelude::*,
+    spi::{Mode, Phase, Polarity, Spi},
+};
+
+#[entry]
+fn main() -> ! {
+    let dp = pac::Peripherals::take().unwrap();
+
+    let rcc = dp.RCC.constrain();
+    let clocks = rcc.cfgr.sysclk(84.mhz()).freeze();
+
+    let gpioa = dp.GPIOA.split();
+    let sck = gpioa.pa5.into_alternate();
+    let miso = gpioa.pa6.into_alternate();
+    let mosi = gpioa.pa7.into_alternate();
+
+    let mode = Mode {
+        polarity: Polarity::IdleLow,
+        phase: Phase::CaptureOnFirstTransition,
+    };
+    let spi = Spi::new(dp.SPI1, (sck, miso, mosi), mode, 1.mhz().into(), clocks);
+
+    // SPI1_TX is on DMA2 stream 3 and SPI1_RX on DMA2 stream 0, both on channel 3
+    let streams = StreamsTuple::new(dp.DMA2);
+
+    let tx_buf = cortex_m::singleton!(: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+    let rx_buf = cortex_m::singleton!(: [u8; 8] = [0; 8]).unwrap();
+
+    let transfer = spi.dma_transfer(streams.3, streams.0, tx_buf, rx_buf);
+    let (spi, _tx_stream, _rx_stream, tx_buf, rx_buf) = transfer.wait();
+
+    assert_eq!(tx_buf, rx_buf);
+
+    let _ = spi.release();
+
+    loop {
+        cortex_m::asm::nop();
+    }
+}
@@ -0,0 +1,614 @@
+//! Input capture
+//!
+//! Captures the value of the timer counter on an edge of the channel inputs, to timestamp pulses
+//! or to measure the time between them. The counter runs freely over its whole range, so the time
+//! between two captures is `later.wrapping_sub(earlier)` ticks.
+
+use crate::dma::traits::PeriAddress;
+use crate::dma::traits::{DMASet, CCR1, CCR2, CCR3, CCR4};
+use crate::dma::PeripheralToMemory;
+use crate::pwm::{C1, C2, C3, C4};
+use crate::{
+    bb,
+    time::Hertz,
+    timer::{self, Timer},
+};
+use core::{marker::PhantomData, mem::MaybeUninit};
+
+use crate::pac::{TIM1, TIM11, TIM9};
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+use crate::pac::{TIM10, TIM2, TIM3, TIM4, TIM5};
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+use crate::pac::{TIM12, TIM13, TIM14, TIM8};
+
+use crate::timer::PinC1;
+use crate::timer::PinC2;
+use crate::timer::PinC3;
+use crate::timer::PinC4;
+
+/// Input capture error
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Error {
+    /// A capture happened before the previous one was read, the previous one is lost
+    Overcapture,
+    /// The tick rate can not be derived from the timer clock
+    Frequency,
+}
+
+/// Input edge on which the counter is captured
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Edge {
+    /// Rising edge
+    Rising,
+    /// Falling edge
+    Falling,
+    /// Both edges
+    ///
+    /// Not available on channel 4 of TIM1 and TIM8, where it captures on falling edges only.
+    Both,
+}
+
+/// Input prescaler, captures once every N edges
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Prescaler {
+    /// Capture on every edge
+    Div1 = 0,
+    /// Capture once every 2 edges
+    Div2 = 1,
+    /// Capture once every 4 edges
+    Div4 = 2,
+    /// Capture once every 8 edges
+    Div8 = 3,
+}
+
+/// Input capture configuration, applied to all the channels
+#[derive(Debug, Copy, Clone)]
+pub struct Config {
+    /// Input edge
+    pub edge: Edge,
+    /// Input prescaler
+    pub prescaler: Prescaler,
+    /// Digital filter, the `ICxF` value from 0 (no filter) to 15
+    pub filter: u8,
+    /// Counter frequency, the timer clock when `None`
+    pub tick_rate: Option<Hertz>,
+}
+
+impl Config {
+    pub fn edge(mut self, edge: Edge) -> Self {
+        self.edge = edge;
+        self
+    }
+
+    pub fn prescaler(mut self, prescaler: Prescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+
+    pub fn filter(mut self, filter: u8) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn tick_rate<T: Into<Hertz>>(mut self, tick_rate: T) -> Self {
+        self.tick_rate = Some(tick_rate.into());
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            edge: Edge::Rising,
+            prescaler: Prescaler::Div1,
+            filter: 0,
+            tick_rate: None,
+        }
+    }
+}
+
+pub trait Pins<TIM, P> {
+    const C1: bool = false;
+    const C2: bool = false;
+    const C3: bool = false;
+    const C4: bool = false;
+    type Channels;
+}
+
+/// Input capture channel of a timer
+pub struct CaptureChannel<TIM, CHANNEL> {
+    _channel: PhantomData<CHANNEL>,
+    _tim: PhantomData<TIM>,
+}
+
+macro_rules! pins_impl {
+    ( $( ( $($PINX:ident),+ ), ( $($TRAIT:ident),+ ), ( $($ENCHX:ident),* ); )+ ) => {
+        $(
+            #[allow(unused_parens)]
+            impl<TIM, $($PINX,)+> Pins<TIM, ($($ENCHX),+)> for ($($PINX),+)
+            where
+                $($PINX: $TRAIT<TIM>,)+
+            {
+                $(const $ENCHX: bool = true;)+
+                type Channels = ($(CaptureChannel<TIM, $ENCHX>),+);
+            }
+        )+
+    };
+}
+
+pins_impl!(
+    (P1, P2, P3, P4), (PinC1, PinC2, PinC3, PinC4), (C1, C2, C3, C4);
+    (P2, P3, P4), (PinC2, PinC3, PinC4), (C2, C3, C4);
+    (P1, P3, P4), (PinC1, PinC3, PinC4), (C1, C3, C4);
+    (P1, P2, P4), (PinC1, PinC2, PinC4), (C1, C2, C4);
+    (P1, P2, P3), (PinC1, PinC2, PinC3), (C1, C2, C3);
+    (P3, P4), (PinC3, PinC4), (C3, C4);
+    (P2, P4), (PinC2, PinC4), (C2, C4);
+    (P2, P3), (PinC2, PinC3), (C2, C3);
+    (P1, P4), (PinC1, PinC4), (C1, C4);
+    (P1, P3), (PinC1, PinC3), (C1, C3);
+    (P1, P2), (PinC1, PinC2), (C1, C2);
+    (P1), (PinC1), (C1);
+    (P2), (PinC2), (C2);
+    (P3), (PinC3), (C3);
+    (P4), (PinC4), (C4);
+);
+
+macro_rules! capture_pin {
+    ($TIMX:ty, $bits:ty, $C:ty, $ccr:ident, $ccmr:ident,
+     $ccs:ident, $icf:ident, $icpsc:ident, $ccp:ident, [$($ccnp:ident)?], $cce:literal,
+     $ccie:ident, $ccif:ident, $ccof:ident) => {
+        impl CaptureChannel<$TIMX, $C> {
+            //NOTE(unsafe) atomic write with no side effects
+            #[inline]
+            pub fn disable(&mut self) {
+                unsafe { bb::clear(&(*<$TIMX>::ptr()).ccer, $cce) }
+            }
+
+            //NOTE(unsafe) atomic write with no side effects
+            #[inline]
+            pub fn enable(&mut self) {
+                unsafe { bb::set(&(*<$TIMX>::ptr()).ccer, $cce) }
+            }
+
+            /// Returns the last captured counter value, if there is a new one.
+            ///
+            /// Returns `Error::Overcapture` once if captures were lost since the last call, the
+            /// next call returns the latest capture.
+            pub fn capture(&mut self) -> nb::Result<$bits, Error> {
+                let tim = unsafe { &*<$TIMX>::ptr() };
+                let sr = tim.sr.read();
+                if sr.$ccof().bit_is_set() {
+                    // NOTE(unsafe) writing 1 to the other flags has no effect
+                    tim.sr
+                        .write(|w| unsafe { w.bits(0xffff) }.$ccof().clear_bit());
+                    Err(nb::Error::Other(Error::Overcapture))
+                } else if sr.$ccif().bit_is_set() {
+                    // Reading the capture register clears the interrupt flag
+                    Ok(self.get_capture())
+                } else {
+                    Err(nb::Error::WouldBlock)
+                }
+            }
+
+            //NOTE(unsafe) atomic read with no side effects
+            /// Returns the value of the capture register, without checking for a new capture.
+            #[inline]
+            pub fn get_capture(&self) -> $bits {
+                unsafe { (*<$TIMX>::ptr()).$ccr.read().ccr().bits() as $bits }
+            }
+
+            /// Returns `true` if a capture was lost since the last capture was read.
+            #[inline]
+            pub fn is_overcapture(&self) -> bool {
+                unsafe { (*<$TIMX>::ptr()).sr.read().$ccof().bit_is_set() }
+            }
+
+            /// Selects the input edge.
+            #[allow(unused_variables)]
+            pub fn set_edge(&mut self, edge: Edge) {
+                let (p, np) = match edge {
+                    Edge::Rising => (false, false),
+                    Edge::Falling => (true, false),
+                    Edge::Both => (true, true),
+                };
+                unsafe {
+                    (*<$TIMX>::ptr())
+                        .ccer
+                        .modify(|_, w| w.$ccp().bit(p)$(.$ccnp().bit(np))?)
+                }
+            }
+
+            /// Selects the input prescaler.
+            #[allow(unused_unsafe)]
+            pub fn set_prescaler(&mut self, prescaler: Prescaler) {
+                unsafe {
+                    (*<$TIMX>::ptr())
+                        .$ccmr()
+                        .modify(|_, w| w.$icpsc().bits(prescaler as u8))
+                }
+            }
+
+            /// Selects the digital filter, the `ICxF` value from 0 (no filter) to 15.
+            #[allow(unused_unsafe)]
+            pub fn set_filter(&mut self, filter: u8) {
+                unsafe {
+                    (*<$TIMX>::ptr())
+                        .$ccmr()
+                        .modify(|_, w| w.$icf().bits(filter & 0xf))
+                }
+            }
+
+            /// Starts listening for capture interrupts.
+            #[inline]
+            pub fn listen(&mut self) {
+                unsafe { (*<$TIMX>::ptr()).dier.modify(|_, w| w.$ccie().set_bit()) }
+            }
+
+            /// Stops listening for capture interrupts.
+            #[inline]
+            pub fn unlisten(&mut self) {
+                unsafe { (*<$TIMX>::ptr()).dier.modify(|_, w| w.$ccie().clear_bit()) }
+            }
+
+            #[allow(unused_unsafe)]
+            fn configure(&mut self, config: &Config) {
+                // Map the channel on its own input
+                unsafe {
+                    (*<$TIMX>::ptr())
+                        .$ccmr()
+                        .modify(|_, w| w.$ccs().bits(0b01))
+                };
+                self.set_edge(config.edge);
+                self.set_prescaler(config.prescaler);
+                self.set_filter(config.filter);
+                self.enable();
+            }
+        }
+    };
+}
+
+macro_rules! capture_dma {
+    ($TIMX:ty, $bits:ty, $C:ty, $CCR:ident, $ccr:ident, $ccde:ident) => {
+        impl CaptureChannel<$TIMX, $C> {
+            /// Starts requesting a DMA transfer on each capture.
+            #[inline]
+            pub fn listen_dma(&mut self) {
+                unsafe { (*<$TIMX>::ptr()).dier.modify(|_, w| w.$ccde().set_bit()) }
+            }
+
+            /// Stops requesting DMA transfers.
+            #[inline]
+            pub fn unlisten_dma(&mut self) {
+                unsafe { (*<$TIMX>::ptr()).dier.modify(|_, w| w.$ccde().clear_bit()) }
+            }
+        }
+
+        unsafe impl PeriAddress for CaptureChannel<$TIMX, $C> {
+            #[inline(always)]
+            fn address(&self) -> u32 {
+                unsafe { &(*<$TIMX>::ptr()).$ccr as *const _ as u32 }
+            }
+
+            type MemSize = $bits;
+        }
+
+        /// DMA is available for a capture channel based on the mappings for the channel
+        unsafe impl<STREAM, const CHANNEL: u8> DMASet<STREAM, PeripheralToMemory, CHANNEL>
+            for CaptureChannel<$TIMX, $C>
+        where
+            $CCR<$TIMX>: DMASet<STREAM, PeripheralToMemory, CHANNEL>,
+        {
+        }
+    };
+}
+
+macro_rules! capture_all_channels {
+    ($($TIMX:ident: ($bits:ty, [$($c4np:ident)?]),)+) => {
+        $(
+            impl Timer<$TIMX> {
+                /// Configures the timer for input capture on the channels of `pins`.
+                ///
+                /// The counter counts up over its whole range at `config.tick_rate`.
+                ///
+                /// Returns `Error::Frequency` if the tick rate cannot be derived from the timer
+                /// clock.
+                pub fn input_capture<P, PINS>(
+                    self,
+                    _pins: PINS,
+                    config: Config,
+                ) -> Result<PINS::Channels, Error>
+                where
+                    PINS: Pins<$TIMX, P>,
+                {
+                    let psc = prescaler(self.clk, config.tick_rate)?;
+                    self.tim.psc.write(|w| w.psc().bits(psc));
+                    self.tim.arr.write(|w| unsafe { w.bits(<$bits>::MAX as u32) });
+
+                    // Trigger update event to load the registers
+                    self.tim.cr1.modify(|_, w| w.urs().set_bit());
+                    self.tim.egr.write(|w| w.ug().set_bit());
+                    self.tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                    if PINS::C1 {
+                        CaptureChannel::<$TIMX, C1> { _channel: PhantomData, _tim: PhantomData }
+                            .configure(&config);
+                    }
+                    if PINS::C2 {
+                        CaptureChannel::<$TIMX, C2> { _channel: PhantomData, _tim: PhantomData }
+                            .configure(&config);
+                    }
+                    if PINS::C3 {
+                        CaptureChannel::<$TIMX, C3> { _channel: PhantomData, _tim: PhantomData }
+                            .configure(&config);
+                    }
+                    if PINS::C4 {
+                        CaptureChannel::<$TIMX, C4> { _channel: PhantomData, _tim: PhantomData }
+                            .configure(&config);
+                    }
+
+                    self.tim.cr1.write(|w|
+                        w.cms()
+                            .bits(0b00)
+                            .dir()
+                            .clear_bit()
+                            .opm()
+                            .clear_bit()
+                            .cen()
+                            .set_bit()
+                    );
+                    //NOTE(unsafe) `PINS::Channels` is a ZST
+                    Ok(unsafe { MaybeUninit::uninit().assume_init() })
+                }
+            }
+
+            capture_pin!($TIMX, $bits, C1, ccr1, ccmr1_input, cc1s, ic1f, ic1psc, cc1p, [cc1np], 0, cc1ie, cc1if, cc1of);
+            capture_pin!($TIMX, $bits, C2, ccr2, ccmr1_input, cc2s, ic2f, ic2psc, cc2p, [cc2np], 4, cc2ie, cc2if, cc2of);
+            capture_pin!($TIMX, $bits, C3, ccr3, ccmr2_input, cc3s, ic3f, ic3psc, cc3p, [cc3np], 8, cc3ie, cc3if, cc3of);
+            capture_pin!($TIMX, $bits, C4, ccr4, ccmr2_input, cc4s, ic4f, ic4psc, cc4p, [$($c4np)?], 12, cc4ie, cc4if, cc4of);
+
+            capture_dma!($TIMX, $bits, C1, CCR1, ccr1, cc1de);
+            capture_dma!($TIMX, $bits, C2, CCR2, ccr2, cc2de);
+            capture_dma!($TIMX, $bits, C3, CCR3, ccr3, cc3de);
+            capture_dma!($TIMX, $bits, C4, CCR4, ccr4, cc4de);
+        )+
+    };
+}
+
+macro_rules! capture_2_channels {
+    ($($TIMX:ident,)+) => {
+        $(
+            impl Timer<$TIMX> {
+                /// Configures the timer for input capture on the channels of `pins`.
+                ///
+                /// The counter counts up over its whole range at `config.tick_rate`.
+                ///
+                /// Returns `Error::Frequency` if the tick rate cannot be derived from the timer
+                /// clock.
+                pub fn input_capture<P, PINS>(
+                    self,
+                    _pins: PINS,
+                    config: Config,
+                ) -> Result<PINS::Channels, Error>
+                where
+                    PINS: Pins<$TIMX, P>,
+                {
+                    let psc = prescaler(self.clk, config.tick_rate)?;
+                    self.tim.psc.write(|w| w.psc().bits(psc));
+                    self.tim.arr.write(|w| unsafe { w.bits(0xffff) });
+
+                    // Trigger update event to load the registers
+                    self.tim.cr1.modify(|_, w| w.urs().set_bit());
+                    self.tim.egr.write(|w| w.ug().set_bit());
+                    self.tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                    if PINS::C1 {
+                        CaptureChannel::<$TIMX, C1> { _channel: PhantomData, _tim: PhantomData }
+                            .configure(&config);
+                    }
+                    if PINS::C2 {
+                        CaptureChannel::<$TIMX, C2> { _channel: PhantomData, _tim: PhantomData }
+                            .configure(&config);
+                    }
+
+                    self.tim.cr1.write(|w| w.opm().clear_bit().cen().set_bit());
+                    //NOTE(unsafe) `PINS::Channels` is a ZST
+                    Ok(unsafe { MaybeUninit::uninit().assume_init() })
+                }
+            }
+
+            capture_pin!($TIMX, u16, C1, ccr1, ccmr1_input, cc1s, ic1f, ic1psc, cc1p, [cc1np], 0, cc1ie, cc1if, cc1of);
+            capture_pin!($TIMX, u16, C2, ccr2, ccmr1_input, cc2s, ic2f, ic2psc, cc2p, [cc2np], 4, cc2ie, cc2if, cc2of);
+        )+
+    };
+}
+
+macro_rules! capture_1_channel {
+    ($($TIMX:ident,)+) => {
+        $(
+            impl Timer<$TIMX> {
+                /// Configures the timer for input capture on the channel of `pins`.
+                ///
+                /// The counter counts up over its whole range at `config.tick_rate`.
+                ///
+                /// Returns `Error::Frequency` if the tick rate cannot be derived from the timer
+                /// clock.
+                pub fn input_capture<P, PINS>(
+                    self,
+                    _pins: PINS,
+                    config: Config,
+                ) -> Result<PINS::Channels, Error>
+                where
+                    PINS: Pins<$TIMX, P>,
+                {
+                    let psc = prescaler(self.clk, config.tick_rate)?;
+                    self.tim.psc.write(|w| w.psc().bits(psc));
+                    self.tim.arr.write(|w| unsafe { w.bits(0xffff) });
+
+                    // Trigger update event to load the registers
+                    self.tim.cr1.modify(|_, w| w.urs().set_bit());
+                    self.tim.egr.write(|w| w.ug().set_bit());
+                    self.tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                    if PINS::C1 {
+                        CaptureChannel::<$TIMX, C1> { _channel: PhantomData, _tim: PhantomData }
+                            .configure(&config);
+                    }
+
+                    self.tim.cr1.write(|w| w.cen().set_bit());
+                    //NOTE(unsafe) `PINS::Channels` is a ZST
+                    Ok(unsafe { MaybeUninit::uninit().assume_init() })
+                }
+            }
+
+            capture_pin!($TIMX, u16, C1, ccr1, ccmr1_input, cc1s, ic1f, ic1psc, cc1p, [cc1np], 0, cc1ie, cc1if, cc1of);
+        )+
+    };
+}
+
+/// Returns the prescaler value dividing `clk` down to `tick_rate`
+fn prescaler(clk: Hertz, tick_rate: Option<Hertz>) -> Result<u16, Error> {
+    match tick_rate {
+        Some(tick_rate) => timer::prescaler(clk, tick_rate).map_err(|_| Error::Frequency),
+        None => Ok(0),
+    }
+}
+
+capture_all_channels!(TIM1: (u16, []),);
+
+capture_2_channels!(TIM9,);
+
+capture_1_channel!(TIM11,);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+capture_all_channels!(
+    TIM2: (u32, [cc4np]),
+    TIM3: (u16, [cc4np]),
+    TIM4: (u16, [cc4np]),
+    TIM5: (u32, [cc4np]),
+);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+capture_all_channels!(TIM8: (u16, []),);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+capture_1_channel!(TIM10,);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+capture_2_channels!(TIM12,);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+capture_1_channel!(TIM13, TIM14,);
@@ -0,0 +1,308 @@
+//! External pulse counting
+//!
+//! The counter of a timer can be clocked by an external signal instead of the internal clock:
+//! by the edges of the channel 1 or 2 input (external clock mode 1), or by the edges of the
+//! external trigger input ETR, after a prescaler and a filter (external clock mode 2). The
+//! signal is sampled by the timer clock, ETR pulses faster than a quarter of the timer clock need
+//! the prescaler. Open-collector sensors need a pull-up, e.g. `internal_pull_up(true)` on the pin.
+//!
+//! A gated frequency counter is built from a counter clocked by ETR and a gate timer: the gate
+//! timer is set up with `set_master_mode(MasterMode::Enable)` to drive its trigger output while it
+//! counts, `PulseCounter::set_gate` makes the counter count only during that window, and the
+//! frequency is the count divided by the window length.
+
+use crate::capture::Prescaler;
+use crate::timer::{InternalTrigger, Timer};
+
+use crate::pac::{TIM1, TIM5};
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+use crate::pac::{TIM2, TIM3, TIM4};
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+use crate::pac::TIM8;
+
+use crate::timer::PinC1;
+use crate::timer::PinC2;
+use crate::timer::PinEtr;
+
+/// Counted edge of the input
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Edge {
+    /// Rising edge
+    Rising,
+    /// Falling edge
+    Falling,
+}
+
+/// External clock configuration
+#[derive(Debug, Copy, Clone)]
+pub struct Config {
+    /// Counted edge
+    pub edge: Edge,
+    /// Input prescaler, counts once every N edges. Only available on ETR.
+    pub prescaler: Prescaler,
+    /// Digital filter, the `ICxF`/`ETF` value from 0 (no filter) to 15
+    pub filter: u8,
+}
+
+impl Config {
+    pub fn edge(mut self, edge: Edge) -> Self {
+        self.edge = edge;
+        self
+    }
+
+    pub fn prescaler(mut self, prescaler: Prescaler) -> Self {
+        self.prescaler = prescaler;
+        self
+    }
+
+    pub fn filter(mut self, filter: u8) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            edge: Edge::Rising,
+            prescaler: Prescaler::Div1,
+            filter: 0,
+        }
+    }
+}
+
+/// Timer counting the edges of an external signal
+pub struct PulseCounter<TIM> {
+    tim: TIM,
+}
+
+macro_rules! counter {
+    ($($TIMX:ident: $bits:ty,)+) => {
+        $(
+            impl Timer<$TIMX> {
+                /// Counts the edges of the channel 1 input, in external clock mode 1
+                #[allow(unused_unsafe)]
+                pub fn count_ti1<PIN: PinC1<$TIMX>>(self, _pin: PIN, config: Config) -> PulseCounter<$TIMX> {
+                    let tim = self.tim;
+                    tim.ccmr1_input()
+                        .write(|w| unsafe { w.cc1s().bits(0b01).ic1f().bits(config.filter & 0xf) });
+                    tim.ccer.write(|w| w.cc1p().bit(config.edge == Edge::Falling));
+                    // TI1FP1 clocks the counter
+                    tim.smcr.write(|w| unsafe { w.ts().bits(0b101).sms().bits(0b111) });
+                    PulseCounter::<$TIMX>::start_counting(tim)
+                }
+
+                /// Counts the edges of the channel 2 input, in external clock mode 1
+                #[allow(unused_unsafe)]
+                pub fn count_ti2<PIN: PinC2<$TIMX>>(self, _pin: PIN, config: Config) -> PulseCounter<$TIMX> {
+                    let tim = self.tim;
+                    tim.ccmr1_input()
+                        .write(|w| unsafe { w.cc2s().bits(0b01).ic2f().bits(config.filter & 0xf) });
+                    tim.ccer.write(|w| w.cc2p().bit(config.edge == Edge::Falling));
+                    // TI2FP2 clocks the counter
+                    tim.smcr.write(|w| unsafe { w.ts().bits(0b110).sms().bits(0b111) });
+                    PulseCounter::<$TIMX>::start_counting(tim)
+                }
+            }
+
+            impl PulseCounter<$TIMX> {
+                fn start_counting(tim: $TIMX) -> Self {
+                    tim.psc.write(|w| w.psc().bits(0));
+                    tim.arr.write(|w| unsafe { w.bits(<$bits>::MAX as u32) });
+                    // Trigger update event to load the registers
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+                    tim.egr.write(|w| w.ug().set_bit());
+                    tim.cr1.modify(|_, w| w.urs().clear_bit());
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+                    PulseCounter { tim }
+                }
+
+                /// Returns the number of counted edges, wrapping over the counter range
+                pub fn count(&self) -> $bits {
+                    self.tim.cnt.read().bits() as $bits
+                }
+
+                /// Resets the count to zero
+                pub fn reset(&mut self) {
+                    self.tim.cnt.reset();
+                }
+
+                /// Starts counting
+                pub fn start(&mut self) {
+                    self.tim.cr1.modify(|_, w| w.cen().set_bit());
+                }
+
+                /// Stops counting, the count is kept
+                pub fn stop(&mut self) {
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                }
+
+                /// Stops the counter and releases the TIM peripheral
+                pub fn release(self) -> $TIMX {
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    self.tim.smcr.reset();
+                    self.tim
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! counter_etr {
+    ($($TIMX:ident,)+) => {
+        $(
+            impl Timer<$TIMX> {
+                /// Counts the edges of the external trigger input, in external clock mode 2
+                #[allow(unused_unsafe)]
+                pub fn count_etr<PIN: PinEtr<$TIMX>>(self, _pin: PIN, config: Config) -> PulseCounter<$TIMX> {
+                    let tim = self.tim;
+                    tim.smcr.write(|w| unsafe {
+                        w.ece()
+                            .set_bit()
+                            .etp()
+                            .bit(config.edge == Edge::Falling)
+                            .etps()
+                            .bits(config.prescaler as u8)
+                            .etf()
+                            .bits(config.filter & 0xf)
+                    });
+                    PulseCounter::<$TIMX>::start_counting(tim)
+                }
+            }
+
+            impl PulseCounter<$TIMX> {
+                /// Counts only while the trigger output of `MASTER` is high, for a gated frequency
+                /// counter. Only available when counting the external trigger input.
+                ///
+                /// `MASTER` is selected with a turbofish, e.g. `set_gate::<TIM2>()`.
+                #[allow(unused_unsafe)]
+                pub fn set_gate<MASTER>(&mut self)
+                where
+                    $TIMX: InternalTrigger<MASTER>,
+                {
+                    let itr = <$TIMX as InternalTrigger<MASTER>>::ITR;
+                    self.tim.smcr.modify(|_, w| unsafe { w.ts().bits(itr).sms().bits(0b101) });
+                }
+
+                /// Counts continuously, without gate
+                #[allow(unused_unsafe)]
+                pub fn clear_gate(&mut self) {
+                    self.tim.smcr.modify(|_, w| unsafe { w.sms().bits(0) });
+                }
+            }
+        )+
+    };
+}
+
+counter!(TIM1: u16, TIM5: u32,);
+counter_etr!(TIM1,);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+counter!(TIM2: u32, TIM3: u16, TIM4: u16,);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+counter_etr!(TIM2, TIM3, TIM4,);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+counter!(TIM8: u16,);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+counter_etr!(TIM8,);
@@ -3,8 +3,9 @@
 mod syst;
 
 use cortex_m::peripheral::SYST;
+use embedded_hal::blocking::delay::DelayUs;
 
-use crate::time::Hertz;
+use crate::time::{Hertz, MicroSeconds};
 
 /// Timer as a delay provider (SysTick by default)
 pub struct Delay<T = SYST> {
@@ -19,4 +20,14 @@ impl<T> Delay<T> {
     }
 }
 
+impl<T> Delay<T>
+where
+    Self: DelayUs<u32>,
+{
+    /// Sleeps for `duration`, e.g. `delay.delay_for(2.secs())`
+    pub fn delay_for<D: Into<MicroSeconds>>(&mut self, duration: D) {
+        self.delay_us(duration.into().0);
+    }
+}
+
 mod timer;
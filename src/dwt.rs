@@ -1,7 +1,7 @@
 //! Debug and trace and stuff
 
 use crate::rcc::Clocks;
-use crate::time::Hertz;
+use crate::time::{Hertz, MicroSeconds, NanoSeconds, Period};
 use cortex_m::peripheral::{DCB, DWT};
 use embedded_hal::blocking::delay::{DelayMs, DelayUs};
 
@@ -69,6 +69,15 @@ impl Delay {
         let ticks = duration.ticks as u64;
         Delay::delay_ticks(DWT::get_cycle_count(), ticks);
     }
+    /// Delay for `duration`, e.g. `delay.delay_for(2.secs())`
+    pub fn delay_for<D: Into<Period>>(&mut self, duration: D) {
+        let start = DWT::get_cycle_count();
+        let ticks = duration
+            .into()
+            .cycles(self.clock)
+            .expect("delay out of range");
+        Delay::delay_ticks(start, ticks);
+    }
     /// Delay ticks
     /// NOTE DCB and DWT need to be set up for this to work, so it is private
     fn delay_ticks(mut start: u32, ticks: u64) {
@@ -208,4 +217,18 @@ impl ClockDuration {
     pub fn as_secs_f64(self) -> f64 {
         self.ticks as f64 / self.clock.0 as f64
     }
+    /// Returns the duration in microseconds, `None` if it does not fit
+    pub fn to_micros(self) -> Option<MicroSeconds> {
+        cast::u32(self.as_micros()).ok().map(MicroSeconds)
+    }
+    /// Returns the duration in nanoseconds, `None` if it does not fit
+    pub fn to_nanos(self) -> Option<NanoSeconds> {
+        cast::u32(self.as_nanos()).ok().map(NanoSeconds)
+    }
+}
+
+impl From<ClockDuration> for Period {
+    fn from(duration: ClockDuration) -> Self {
+        Period::Nanos(duration.as_nanos())
+    }
 }
@@ -61,6 +61,19 @@ pub use erased::{EPin, ErasedPin};
 /// A filler pin type
 pub struct NoPin;
 
+/// Setting the level of a filler pin does nothing, for optional chip select pins
+impl OutputPin for NoPin {
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
 /// Extension trait to split a GPIO peripheral in independent pins and registers
 pub trait GpioExt {
     /// The parts to split the GPIO into
@@ -532,6 +545,34 @@ impl<const P: char, const N: u8> InputPin for Pin<Output<OpenDrain>, P, N> {
     }
 }
 
+impl<const P: char, const N: u8, const A: u8> Pin<Alternate<A>, P, N> {
+    /// Reads the pin level, e.g. of a timer input
+    #[inline(always)]
+    pub fn is_high(&self) -> bool {
+        !self.is_low()
+    }
+
+    /// Reads the pin level, e.g. of a timer input
+    #[inline(always)]
+    pub fn is_low(&self) -> bool {
+        self._is_low()
+    }
+}
+
+impl<const P: char, const N: u8, const A: u8> InputPin for Pin<Alternate<A>, P, N> {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.is_high())
+    }
+
+    #[inline(always)]
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(self.is_low())
+    }
+}
+
 impl<MODE, const P: char, const N: u8> Pin<Input<MODE>, P, N> {
     #[inline(always)]
     pub fn is_high(&self) -> bool {
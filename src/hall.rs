@@ -0,0 +1,351 @@
+//! Hall sensor interface
+//!
+//! The three hall sensor inputs of a brushless motor are XORed on the timer input 1, so that the
+//! counter is captured and reset on every hall transition: the captured value is the time between
+//! two transitions, from which the motor speed is derived. The rotor sector is read from the
+//! levels of the inputs.
+//!
+//! The trigger output goes high a configurable delay after each transition. A complementary PWM
+//! timer (TIM1 or TIM8) set up with `PwmChannels::enable_commutation_trigger` uses it to apply the
+//! next step of six-step control, which is preloaded on its channels in the commutation
+//! interrupt.
+
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::InputPin;
+
+use crate::{
+    time::Hertz,
+    timer::{self, Error, Flags, Timer},
+};
+
+use crate::pac::{TIM1, TIM5};
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+use crate::pac::{TIM2, TIM3, TIM4};
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+use crate::pac::TIM8;
+
+use crate::timer::PinC1;
+use crate::timer::PinC2;
+use crate::timer::PinC3;
+
+/// Update interrupt flag
+const UIF: u32 = 1 << 0;
+/// Capture/compare 2 interrupt flag
+const CC2IF: u32 = 1 << 2;
+
+/// The three hall sensor inputs, on channels 1 to 3
+pub trait Pins<TIM> {
+    /// Returns the levels of the inputs, input 1 in bit 0
+    fn state(&self) -> u8;
+}
+
+impl<TIM, P1, P2, P3> Pins<TIM> for (P1, P2, P3)
+where
+    P1: PinC1<TIM> + InputPin<Error = Infallible>,
+    P2: PinC2<TIM> + InputPin<Error = Infallible>,
+    P3: PinC3<TIM> + InputPin<Error = Infallible>,
+{
+    fn state(&self) -> u8 {
+        let high = |pin: &dyn InputPin<Error = Infallible>| matches!(pin.is_high(), Ok(true)) as u8;
+        high(&self.0) | high(&self.1) << 1 | high(&self.2) << 2
+    }
+}
+
+/// Interrupt events
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Event {
+    /// Hall transition
+    Transition,
+    /// Trigger output, the commutation delay after a transition
+    Commutation,
+    /// No transition during a whole counter period, the motor is stalled
+    Stall,
+}
+
+/// Hall sensor interface configuration
+#[derive(Debug, Copy, Clone)]
+pub struct Config {
+    /// Digital filter of the XORed input, the `IC1F` value from 0 (no filter) to 15
+    pub filter: u8,
+    /// Counter frequency, the timer clock when `None`
+    pub tick_rate: Option<Hertz>,
+    /// Ticks from a transition to the trigger output, from 1 to the counter maximum
+    pub commutation_delay: u32,
+}
+
+impl Config {
+    pub fn filter(mut self, filter: u8) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn tick_rate<T: Into<Hertz>>(mut self, tick_rate: T) -> Self {
+        self.tick_rate = Some(tick_rate.into());
+        self
+    }
+
+    pub fn commutation_delay(mut self, ticks: u32) -> Self {
+        self.commutation_delay = ticks;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            filter: 0,
+            tick_rate: None,
+            commutation_delay: 1,
+        }
+    }
+}
+
+/// Hall sensor interface on a timer
+pub struct HallSensor<TIM, PINS> {
+    tim: TIM,
+    pins: PINS,
+    tick_rate: Hertz,
+    period: Option<u32>,
+}
+
+impl<TIM, PINS> HallSensor<TIM, PINS>
+where
+    PINS: Pins<TIM>,
+{
+    /// Returns the levels of the hall inputs, input 1 in bit 0
+    pub fn state(&self) -> u8 {
+        self.pins.state()
+    }
+
+    /// Returns the rotor sector from 0 to 5 for sensors spaced by 120°, or `None` if the inputs
+    /// are all low or all high
+    ///
+    /// The sector increases in the sequence 0b001, 0b011, 0b010, 0b110, 0b100, 0b101 of
+    /// [`state`](Self::state).
+    pub fn sector(&self) -> Option<u8> {
+        match self.state() {
+            0b001 => Some(0),
+            0b011 => Some(1),
+            0b010 => Some(2),
+            0b110 => Some(3),
+            0b100 => Some(4),
+            0b101 => Some(5),
+            _ => None,
+        }
+    }
+
+    /// Returns the ticks between the last two transitions, or `None` if stalled
+    ///
+    /// It is updated by [`on_interrupt`](Self::on_interrupt).
+    pub fn period(&self) -> Option<u32> {
+        self.period
+    }
+
+    /// Returns the electrical rotation frequency, six transitions per electrical revolution
+    pub fn electrical_frequency(&self) -> Hertz {
+        match self.period {
+            Some(period) if period > 0 => {
+                Hertz((u64::from(self.tick_rate.0) / (6 * u64::from(period))) as u32)
+            }
+            _ => Hertz(0),
+        }
+    }
+
+    /// Returns the counter frequency
+    pub fn tick_rate(&self) -> Hertz {
+        self.tick_rate
+    }
+}
+
+macro_rules! hall {
+    ($($TIMX:ident: $bits:ty,)+) => {
+        $(
+            impl Timer<$TIMX> {
+                /// Configures the timer as a hall sensor interface on `pins`.
+                ///
+                /// Returns `timer::Error::TickRate` if the tick rate cannot be derived from the
+                /// timer clock, and `timer::Error::Delay` if the commutation delay is 0 or does
+                /// not fit in the counter.
+                #[allow(unused_unsafe)]
+                pub fn hall_sensor<PINS>(
+                    self,
+                    pins: PINS,
+                    config: Config,
+                ) -> Result<HallSensor<$TIMX, PINS>, Error>
+                where
+                    PINS: Pins<$TIMX>,
+                {
+                    let psc = match config.tick_rate {
+                        Some(tick_rate) => timer::prescaler(self.clk, tick_rate)?,
+                        None => 0,
+                    };
+                    if !(1..=<$bits>::MAX as u32).contains(&config.commutation_delay) {
+                        return Err(Error::Delay);
+                    }
+                    let tim = self.tim;
+                    tim.psc.write(|w| w.psc().bits(psc));
+                    tim.arr.write(|w| unsafe { w.bits(<$bits>::MAX as u32) });
+
+                    // XOR of the 3 inputs on TI1, trigger output on OC2REF
+                    tim.cr2.write(|w| unsafe { w.ti1s().set_bit().mms().bits(0b101) });
+                    // Capture 1 on TRC, the edges of TI1
+                    tim.ccmr1_input()
+                        .write(|w| unsafe { w.cc1s().bits(0b11).ic1f().bits(config.filter & 0xf) });
+                    // Compare 2 in PWM mode 2, high from the commutation delay on
+                    tim.ccmr1_output().modify(|_, w| unsafe { w.oc2m().bits(0b111) });
+                    tim.ccr2.write(|w| unsafe { w.bits(config.commutation_delay) });
+                    tim.ccer.write(|w| w.cc1e().set_bit());
+                    // Reset the counter on the edges of TI1
+                    tim.smcr.write(|w| unsafe { w.ts().bits(0b100).sms().bits(0b100) });
+
+                    // Only counter overflows, when stalled, generate update interrupts
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+                    tim.egr.write(|w| w.ug().set_bit());
+                    tim.sr.write(|w| unsafe { w.bits(0) });
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                    Ok(HallSensor {
+                        tim,
+                        pins,
+                        tick_rate: Hertz(self.clk.0 / (u32::from(psc) + 1)),
+                        period: None,
+                    })
+                }
+            }
+
+            impl<PINS> HallSensor<$TIMX, PINS> {
+                /// Updates the period on a transition or a stall, call this from the timer
+                /// interrupt
+                pub fn on_interrupt(&mut self) {
+                    let sr = self.tim.sr.read();
+                    let overflow = sr.uif().bit_is_set();
+                    let clear = sr.bits() & (UIF | CC2IF);
+                    if clear != 0 {
+                        // NOTE(unsafe) writing 1 to the other flags has no effect
+                        self.tim
+                            .sr
+                            .write(|w| unsafe { w.bits(<$TIMX as Flags>::FLAGS & !clear) });
+                    }
+                    if sr.cc1if().bit_is_set() {
+                        // reading the capture clears the flag, it is not a period after an
+                        // overflow
+                        let captured = self.tim.ccr1.read().bits();
+                        self.period = if overflow { None } else { Some(captured) };
+                    } else if overflow {
+                        self.period = None;
+                    }
+                }
+
+                /// Set the ticks from a transition to the trigger output
+                ///
+                /// Returns `timer::Error::Delay` if `ticks` is 0.
+                pub fn set_commutation_delay(&mut self, ticks: $bits) -> Result<(), Error> {
+                    if ticks == 0 {
+                        return Err(Error::Delay);
+                    }
+                    self.tim.ccr2.write(|w| unsafe { w.bits(ticks.into()) });
+                    Ok(())
+                }
+
+                /// Starts listening for an `event`
+                pub fn listen(&mut self, event: Event) {
+                    match event {
+                        Event::Transition => self.tim.dier.modify(|_, w| w.cc1ie().set_bit()),
+                        Event::Commutation => self.tim.dier.modify(|_, w| w.cc2ie().set_bit()),
+                        Event::Stall => self.tim.dier.modify(|_, w| w.uie().set_bit()),
+                    }
+                }
+
+                /// Stops listening for an `event`
+                pub fn unlisten(&mut self, event: Event) {
+                    match event {
+                        Event::Transition => self.tim.dier.modify(|_, w| w.cc1ie().clear_bit()),
+                        Event::Commutation => self.tim.dier.modify(|_, w| w.cc2ie().clear_bit()),
+                        Event::Stall => self.tim.dier.modify(|_, w| w.uie().clear_bit()),
+                    }
+                }
+
+                /// Stops the timer and releases the TIM peripheral and the pins
+                pub fn release(self) -> ($TIMX, PINS) {
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    (self.tim, self.pins)
+                }
+            }
+        )+
+    };
+}
+
+hall!(TIM1: u16, TIM5: u32,);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+hall!(TIM2: u32, TIM3: u16, TIM4: u16,);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+hall!(TIM8: u16,);
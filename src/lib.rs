@@ -114,6 +114,10 @@ pub mod bb;
 ))]
 pub mod can;
 #[cfg(feature = "device-selected")]
+pub mod capture;
+#[cfg(feature = "device-selected")]
+pub mod counter;
+#[cfg(feature = "device-selected")]
 pub mod crc32;
 #[cfg(all(feature = "device-selected", feature = "dac"))]
 pub mod dac;
@@ -122,9 +126,15 @@ pub mod delay;
 #[cfg(feature = "device-selected")]
 pub mod gpio;
 #[cfg(feature = "device-selected")]
+pub mod hall;
+#[cfg(feature = "device-selected")]
 pub mod i2c;
 #[cfg(all(feature = "device-selected", feature = "i2s"))]
 pub mod i2s;
+#[cfg(all(feature = "device-selected", feature = "lptim1"))]
+pub mod lptim;
+#[cfg(feature = "device-selected")]
+pub mod motion;
 #[cfg(all(feature = "device-selected", feature = "usb_fs", feature = "otg-fs"))]
 pub mod otg_fs;
 #[cfg(all(
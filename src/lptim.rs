@@ -0,0 +1,343 @@
+//! Low-power timer
+//!
+//! LPTIM1 is a 16-bit timer which can be clocked from the LSE or LSI oscillator and keeps running
+//! in Stop mode, where its events wake up the core through EXTI line 23. The oscillator has to be
+//! enabled before, e.g. by the RTC.
+//!
+//! Most of the configuration can only be changed while the timer is disabled: the methods
+//! changing it stop the timer, which has to be started again.
+
+use crate::bb;
+use crate::pac::{EXTI, RCC};
+use crate::rcc::{Clocks, Enable, Reset};
+use crate::time::Hertz;
+
+#[cfg(any(feature = "stm32f413", feature = "stm32f423"))]
+use crate::pac::LPTIM as LPTIM1;
+#[cfg(feature = "stm32f410")]
+use crate::pac::LPTIM1;
+
+use crate::gpio::gpiob::{PB5, PB6, PB7, PB8};
+use crate::gpio::gpioc::{PC0, PC1, PC2, PC3};
+use crate::gpio::{Alternate, AlternateOD};
+
+// Pin marker traits
+pub trait PinOut<LPTIM> {}
+pub trait PinIn1<LPTIM> {}
+pub trait PinIn2<LPTIM> {}
+pub trait PinEtr<LPTIM> {}
+
+macro_rules! pin_impl {
+    ( $( $PINC:ident, $PINX:ident, $AF:literal; )+ ) => {
+        $(
+            impl $PINC<LPTIM1> for $PINX<Alternate<$AF>> {}
+            impl $PINC<LPTIM1> for $PINX<AlternateOD<$AF>> {}
+        )+
+    };
+}
+
+pin_impl!(
+    PinIn1, PB5, 1;
+    PinEtr, PB6, 1;
+    PinIn2, PB7, 1;
+    PinOut, PB8, 1;
+
+    PinIn1, PC0, 1;
+    PinOut, PC1, 1;
+    PinIn2, PC2, 1;
+    PinEtr, PC3, 1;
+);
+
+/// EXTI line of the LPTIM1 wakeup events
+const EXTI_LINE: u8 = 23;
+
+/// Kernel clock of the timer
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ClockSource {
+    /// APB1 clock, stopped in Stop mode
+    Pclk = 0,
+    /// HSI oscillator
+    Hsi = 1,
+    /// LSI oscillator
+    Lsi = 2,
+    /// LSE oscillator
+    Lse = 3,
+}
+
+/// Clock prescaler
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Prescaler {
+    Div1 = 0,
+    Div2 = 1,
+    Div4 = 2,
+    Div8 = 3,
+    Div16 = 4,
+    Div32 = 5,
+    Div64 = 6,
+    Div128 = 7,
+}
+
+/// Trigger from the RTC
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum RtcTrigger {
+    /// RTC alarm A
+    AlarmA = 1,
+    /// RTC alarm B
+    AlarmB = 2,
+}
+
+/// Active edge of the trigger
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum TriggerEdge {
+    Rising = 1,
+    Falling = 2,
+    Both = 3,
+}
+
+/// Output polarity
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Polarity {
+    /// The output is high from compare match to the end of the period
+    ActiveHigh,
+    /// The output is low from compare match to the end of the period
+    ActiveLow,
+}
+
+/// Interrupt events
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Event {
+    /// The counter matched the compare value
+    Compare,
+    /// The counter matched the period, at the end of each period
+    AutoReload,
+    /// Active edge of the trigger
+    ExternalTrigger,
+    /// Encoder mode counting direction changed to up
+    Up,
+    /// Encoder mode counting direction changed to down
+    Down,
+}
+
+/// Low-power timer
+pub struct LowPowerTimer<LPTIM> {
+    lptim: LPTIM,
+    clk: Hertz,
+}
+
+impl LowPowerTimer<LPTIM1> {
+    /// Initializes the timer clocked from `source` divided by `prescaler`
+    ///
+    /// The LSE and LSI oscillators have to be already enabled.
+    pub fn new(lptim: LPTIM1, source: ClockSource, prescaler: Prescaler, clocks: &Clocks) -> Self {
+        unsafe {
+            //NOTE(unsafe) this reference will only be used for atomic writes with no side effects
+            let rcc = &(*RCC::ptr());
+            // Enable and reset the timer peripheral
+            LPTIM1::enable(rcc);
+            LPTIM1::reset(rcc);
+            rcc.dckcfgr2.modify(|_, w| w.lptim1sel().bits(source as u8));
+        }
+
+        let clk = match source {
+            ClockSource::Pclk => clocks.pclk1().0,
+            ClockSource::Hsi => 16_000_000,
+            ClockSource::Lsi => 32_000,
+            ClockSource::Lse => 32_768,
+        };
+        lptim
+            .cfgr
+            .write(|w| unsafe { w.presc().bits(prescaler as u8) });
+
+        Self {
+            lptim,
+            clk: Hertz(clk >> (prescaler as u8)),
+        }
+    }
+
+    /// Returns the counter frequency, nominal for the LSI oscillator
+    pub fn frequency(&self) -> Hertz {
+        self.clk
+    }
+
+    /// Starts counting from 0 to `period` repeatedly
+    pub fn start_continuous(&mut self, period: u16) {
+        self.enable(period);
+        self.lptim.cr.modify(|_, w| w.cntstrt().set_bit());
+    }
+
+    /// Counts once from 0 to `period`, the output gives a single pulse
+    pub fn start_one_shot(&mut self, period: u16) {
+        self.enable(period);
+        self.lptim.cr.modify(|_, w| w.sngstrt().set_bit());
+    }
+
+    /// Stops and disables the timer
+    pub fn stop(&mut self) {
+        self.lptim.cr.modify(|_, w| w.enable().clear_bit());
+    }
+
+    /// Returns the counter value
+    ///
+    /// The counter is clocked asynchronously, it is read until two consecutive reads match.
+    pub fn count(&self) -> u16 {
+        loop {
+            let first = self.lptim.cnt.read().bits();
+            if self.lptim.cnt.read().bits() == first {
+                return first as u16;
+            }
+        }
+    }
+
+    /// Sets the compare value, the output is active from compare match to the end of the period.
+    /// The timer has to be started.
+    pub fn set_compare(&mut self, compare: u16) {
+        self.lptim.icr.write(|w| w.cmpokcf().set_bit());
+        self.lptim
+            .cmp
+            .write(|w| unsafe { w.bits(u32::from(compare)) });
+        while self.lptim.isr.read().cmpok().bit_is_clear() {}
+    }
+
+    /// Outputs the PWM waveform on `pin`, or stays active after the first compare match with
+    /// `set_once`. Stops the timer.
+    pub fn set_output<PIN: PinOut<LPTIM1>>(
+        &mut self,
+        _pin: PIN,
+        polarity: Polarity,
+        set_once: bool,
+    ) {
+        self.stop();
+        self.lptim.cfgr.modify(|_, w| {
+            w.wavpol()
+                .bit(polarity == Polarity::ActiveLow)
+                .wave()
+                .bit(set_once)
+        });
+    }
+
+    /// Counts in encoder mode on the `pins` inputs, on both edges of both inputs. The counter
+    /// stays clocked by the kernel clock, which has to be faster than 4 times the input edges.
+    /// Stops the timer.
+    pub fn set_encoder<IN1, IN2>(&mut self, _pins: (IN1, IN2))
+    where
+        IN1: PinIn1<LPTIM1>,
+        IN2: PinIn2<LPTIM1>,
+    {
+        self.stop();
+        self.lptim
+            .cfgr
+            .modify(|_, w| unsafe { w.cksel().clear_bit().enc().set_bit().ckpol().bits(0b10) });
+    }
+
+    /// Starts counting on an edge of the external trigger input on `pin`, or resets the counter
+    /// on each active edge with `timeout`. Stops the timer.
+    pub fn set_external_trigger<PIN: PinEtr<LPTIM1>>(
+        &mut self,
+        _pin: PIN,
+        edge: TriggerEdge,
+        timeout: bool,
+    ) {
+        self.set_trigger(0, edge, timeout);
+    }
+
+    /// Starts counting on an RTC event, or resets the counter on each event with `timeout`.
+    /// Stops the timer.
+    pub fn set_rtc_trigger(&mut self, trigger: RtcTrigger, edge: TriggerEdge, timeout: bool) {
+        self.set_trigger(trigger as u8, edge, timeout);
+    }
+
+    /// Starts counting by software again. Stops the timer.
+    pub fn clear_trigger(&mut self) {
+        self.stop();
+        self.lptim
+            .cfgr
+            .modify(|_, w| unsafe { w.trigen().bits(0).timout().clear_bit() });
+    }
+
+    /// Starts listening for an `event`. Stops the timer.
+    pub fn listen(&mut self, event: Event) {
+        self.stop();
+        self.lptim
+            .ier
+            .modify(|r, w| unsafe { w.bits(r.bits() | Self::mask(event)) });
+    }
+
+    /// Stops listening for an `event`. Stops the timer.
+    pub fn unlisten(&mut self, event: Event) {
+        self.stop();
+        self.lptim
+            .ier
+            .modify(|r, w| unsafe { w.bits(r.bits() & !Self::mask(event)) });
+    }
+
+    /// Returns `true` if `event` is pending
+    pub fn is_pending(&self, event: Event) -> bool {
+        self.lptim.isr.read().bits() & Self::mask(event) != 0
+    }
+
+    /// Clears the interrupt flag of `event`
+    pub fn clear_interrupt(&mut self, event: Event) {
+        self.lptim
+            .icr
+            .write(|w| unsafe { w.bits(Self::mask(event)) });
+    }
+
+    /// Wakes up the core from Stop mode on the events listened to, through EXTI line 23
+    pub fn enable_wakeup(&mut self, exti: &mut EXTI) {
+        unsafe {
+            bb::set(&exti.imr, EXTI_LINE);
+            bb::set(&exti.rtsr, EXTI_LINE);
+        }
+    }
+
+    /// Stops waking up the core from Stop mode
+    pub fn disable_wakeup(&mut self, exti: &mut EXTI) {
+        unsafe {
+            bb::clear(&exti.imr, EXTI_LINE);
+            bb::clear(&exti.rtsr, EXTI_LINE);
+        }
+    }
+
+    /// Clears the EXTI line 23 pending flag, the timer event flags are cleared separately
+    pub fn clear_wakeup(&mut self, exti: &mut EXTI) {
+        exti.pr.write(|w| unsafe { w.bits(1 << EXTI_LINE) });
+    }
+
+    /// Stops the timer and releases the LPTIM peripheral
+    pub fn release(mut self) -> LPTIM1 {
+        self.stop();
+        self.lptim
+    }
+
+    // The period can only be written while the timer is enabled
+    fn enable(&mut self, period: u16) {
+        self.lptim.cr.modify(|_, w| w.enable().set_bit());
+        self.lptim.icr.write(|w| w.arrokcf().set_bit());
+        self.lptim
+            .arr
+            .write(|w| unsafe { w.bits(u32::from(period)) });
+        while self.lptim.isr.read().arrok().bit_is_clear() {}
+    }
+
+    fn set_trigger(&mut self, source: u8, edge: TriggerEdge, timeout: bool) {
+        self.stop();
+        self.lptim.cfgr.modify(|_, w| unsafe {
+            w.trigsel()
+                .bits(source)
+                .trigen()
+                .bits(edge as u8)
+                .timout()
+                .bit(timeout)
+        });
+    }
+
+    fn mask(event: Event) -> u32 {
+        match event {
+            Event::Compare => 1 << 0,
+            Event::AutoReload => 1 << 1,
+            Event::ExternalTrigger => 1 << 2,
+            Event::Up => 1 << 5,
+            Event::Down => 1 << 6,
+        }
+    }
+}
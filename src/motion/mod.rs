@@ -0,0 +1,10 @@
+//! Motion control
+//!
+//! [`servo::Servo`] drives a hobby servo from a PWM channel, the position being set as a pulse
+//! width or an angle. [`stepper::Stepper`] drives a step/dir stepper motor driver from a timer,
+//! moving with the trapezoidal speed profile of [`stepper::Ramp`]: the step pulses are output on a
+//! PWM channel and the interval of each step is written to the auto-reload register from the
+//! update interrupt or by DMA.
+
+pub mod servo;
+pub mod stepper;
@@ -0,0 +1,153 @@
+//! Hobby servos
+//!
+//! A servo expects a pulse every 20 ms or so, its width setting the position, typically from 1 ms
+//! to 2 ms. The PWM channel is set up by the user, e.g. with `Timer::pwm(pin, 50.hz())`, and its
+//! period is given to the servo, e.g. with `PwmChannels::get_period`, to convert pulse widths to
+//! duties.
+
+use core::convert::TryFrom;
+
+use crate::hal::PwmPin;
+use crate::time::{MicroSeconds, Period};
+
+/// Servo error
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Error {
+    /// The PWM period is zero or shorter than the maximum pulse
+    Period,
+    /// The minimum pulse is longer than the maximum pulse
+    PulseRange,
+}
+
+/// Servo configuration
+#[derive(Debug, Copy, Clone)]
+pub struct Config {
+    /// Pulse width at angle 0
+    pub min_pulse: MicroSeconds,
+    /// Pulse width at the end of the range
+    pub max_pulse: MicroSeconds,
+    /// Angle of travel from `min_pulse` to `max_pulse`, in degrees
+    pub range: u16,
+}
+
+impl Config {
+    pub fn min_pulse<T: Into<MicroSeconds>>(mut self, pulse: T) -> Self {
+        self.min_pulse = pulse.into();
+        self
+    }
+
+    pub fn max_pulse<T: Into<MicroSeconds>>(mut self, pulse: T) -> Self {
+        self.max_pulse = pulse.into();
+        self
+    }
+
+    pub fn range(mut self, degrees: u16) -> Self {
+        self.range = degrees;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            min_pulse: MicroSeconds(1_000),
+            max_pulse: MicroSeconds(2_000),
+            range: 180,
+        }
+    }
+}
+
+/// Hobby servo on a PWM channel
+pub struct Servo<PWM> {
+    pwm: PWM,
+    period_ns: u64,
+    config: Config,
+}
+
+impl<PWM> Servo<PWM>
+where
+    PWM: PwmPin,
+    PWM::Duty: Into<u32> + TryFrom<u32>,
+{
+    /// Drives a servo on `pwm`, whose PWM period is `period`. The output is enabled at the middle
+    /// of the range.
+    ///
+    /// Returns `Error::Period` if the period is zero or shorter than `config.max_pulse`, and
+    /// `Error::PulseRange` if `config.min_pulse` is longer than `config.max_pulse`.
+    pub fn new<T: Into<Period>>(pwm: PWM, period: T, config: Config) -> Result<Self, Error> {
+        if config.min_pulse.0 > config.max_pulse.0 {
+            return Err(Error::PulseRange);
+        }
+        let period_ns = match period.into() {
+            Period::Nanos(ns) => ns,
+            Period::Rate(rate) => 1_000_000_000u64
+                .checked_div(u64::from(rate.0))
+                .ok_or(Error::Period)?,
+        };
+        if period_ns == 0 || period_ns < u64::from(config.max_pulse.0) * 1_000 {
+            return Err(Error::Period);
+        }
+        let mut servo = Servo {
+            pwm,
+            period_ns,
+            config,
+        };
+        servo.set_angle(config.range / 2);
+        servo.pwm.enable();
+        Ok(servo)
+    }
+
+    /// Sets the pulse width, limited to the configured range
+    pub fn set_pulse_width<T: Into<MicroSeconds>>(&mut self, pulse: T) {
+        let pulse = pulse
+            .into()
+            .0
+            .max(self.config.min_pulse.0)
+            .min(self.config.max_pulse.0);
+        let period = u64::from(self.pwm.get_max_duty().into()) + 1;
+        let duty = u64::from(pulse) * 1_000 * period / self.period_ns;
+        if let Ok(duty) = PWM::Duty::try_from(duty as u32) {
+            self.pwm.set_duty(duty);
+        }
+    }
+
+    /// Returns the pulse width, rounded down
+    pub fn pulse_width(&self) -> MicroSeconds {
+        let period = u64::from(self.pwm.get_max_duty().into()) + 1;
+        let duty = u64::from(self.pwm.get_duty().into());
+        MicroSeconds((duty * self.period_ns / period / 1_000) as u32)
+    }
+
+    /// Sets the angle in degrees, limited to the configured range
+    pub fn set_angle(&mut self, degrees: u16) {
+        let degrees = u32::from(degrees.min(self.config.range));
+        let span = self.config.max_pulse.0 - self.config.min_pulse.0;
+        let range = u32::from(self.config.range).max(1);
+        self.set_pulse_width(MicroSeconds(
+            self.config.min_pulse.0 + span * degrees / range,
+        ));
+    }
+
+    /// Returns the angle in degrees, rounded down
+    pub fn angle(&self) -> u16 {
+        let span = (self.config.max_pulse.0 - self.config.min_pulse.0).max(1);
+        let offset = self.pulse_width().0.saturating_sub(self.config.min_pulse.0);
+        (offset * u32::from(self.config.range) / span) as u16
+    }
+
+    /// Starts the pulses
+    pub fn enable(&mut self) {
+        self.pwm.enable();
+    }
+
+    /// Stops the pulses, most servos then stop holding their position
+    pub fn disable(&mut self) {
+        self.pwm.disable();
+    }
+
+    /// Stops the pulses and releases the PWM channel
+    pub fn release(mut self) -> PWM {
+        self.pwm.disable();
+        self.pwm
+    }
+}
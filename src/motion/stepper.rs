@@ -0,0 +1,617 @@
+//! Step/dir stepper motors
+//!
+//! The step pulses are output on a PWM channel, one per counter period: the auto-reload register
+//! holds the interval to the next step, minus one, and the duty the pulse width. The intervals
+//! follow a [`Ramp`], accelerating to the maximum speed, cruising and decelerating to stop on the
+//! last step. Each interval is written to the preloaded auto-reload register one step ahead, either
+//! from the update interrupt with [`Stepper::move_by`] or by DMA with [`Stepper::prepare_dma`].
+//!
+//! The slowest step, at the start and the end of the moves, has to fit in the counter: 65536 ticks
+//! on 16-bit timers, e.g. 15 steps per second at a 1 MHz tick rate.
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+use embedded_hal::digital::v2::OutputPin;
+
+use crate::dma::traits::{DMASet, PeriAddress, DMAR};
+use crate::dma::MemoryToPeripheral;
+use crate::hal::PwmPin;
+use crate::pwm::PwmChannels;
+use crate::rcc::{Clocks, GetBusFreq};
+use crate::time::{Hertz, MicroSeconds};
+use crate::timer::{self, Error, Flags};
+
+use crate::pac::{TIM1, TIM5};
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+use crate::pac::{TIM2, TIM3, TIM4};
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+use crate::pac::TIM8;
+
+/// Update interrupt flag
+const UIF: u32 = 1 << 0;
+
+/// Trapezoidal speed profile of a move, as the interval of each step in ticks
+///
+/// The intervals are computed incrementally as in D. Austin, "Generate stepper-motor speed
+/// profiles in real time", with a constant acceleration up to the maximum speed and the same
+/// deceleration to stop on the last step. A short move decelerates before reaching the maximum
+/// speed.
+#[derive(Debug, Copy, Clone)]
+pub struct Ramp {
+    remaining: u32,
+    // interval of the next step in 1/256 ticks, before the maximum speed limit, so that the
+    // deceleration mirrors the acceleration
+    interval: u64,
+    min_interval: u64,
+    // number of acceleration steps done
+    level: u64,
+}
+
+impl Ramp {
+    /// Creates the profile of a move of `steps` steps, with intervals counted at `tick_rate`, a
+    /// maximum speed in steps/s and an acceleration in steps/s².
+    ///
+    /// # Panics
+    ///
+    /// Panics if the maximum speed or the acceleration is 0.
+    pub fn new(steps: u32, tick_rate: Hertz, max_speed: u32, acceleration: u32) -> Self {
+        assert!(max_speed > 0 && acceleration > 0);
+        let tick_rate = u64::from(tick_rate.0);
+        let min_interval = (tick_rate << 8) / u64::from(max_speed);
+        // First interval: 0.676 * f * sqrt(2 / a), sqrt(2 / a) in 1/2^24
+        let root = isqrt((2 << 48) / u64::from(acceleration));
+        let first = ((tick_rate * root) >> 16) * 676 / 1000;
+        Ramp {
+            remaining: steps,
+            interval: first,
+            min_interval,
+            level: 0,
+        }
+    }
+
+    /// Returns the number of steps left
+    pub fn remaining(&self) -> u32 {
+        self.remaining
+    }
+}
+
+impl Iterator for Ramp {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let interval = (self.interval.max(self.min_interval) >> 8)
+            .max(1)
+            .min(u64::from(u32::MAX)) as u32;
+        self.remaining -= 1;
+
+        // Accelerate only if there are enough steps left to decelerate from the next level, then
+        // hold the speed as long as the steps left allow to stop
+        let remaining = u64::from(self.remaining);
+        if remaining >= self.level + 2 && self.interval > self.min_interval {
+            self.level += 1;
+            self.interval -= 2 * self.interval / (4 * self.level + 1);
+        } else if remaining < self.level + 1 && self.level > 0 {
+            // Revert the last acceleration step
+            self.interval += 2 * self.interval / (4 * self.level - 1);
+            self.level -= 1;
+        }
+        Some(interval)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+// Returns the auto-reload value for a step `interval`, a counter period is ARR + 1 ticks
+fn auto_reload(interval: u32, max: u32) -> u32 {
+    interval.saturating_sub(1).max(1).min(max)
+}
+
+fn isqrt(value: u64) -> u64 {
+    if value == 0 {
+        return 0;
+    }
+    // Newton's method from a power of 2 above the root
+    let mut root = 1 << ((64 - value.leading_zeros() + 1) / 2);
+    loop {
+        let next = (root + value / root) / 2;
+        if next >= root {
+            return root;
+        }
+        root = next;
+    }
+}
+
+/// Stepper motor configuration
+#[derive(Debug, Copy, Clone)]
+pub struct Config {
+    /// Counter frequency, the resolution of the step intervals
+    pub tick_rate: Hertz,
+    /// Width of the step pulses
+    pub pulse_width: MicroSeconds,
+    /// Maximum speed, in steps/s
+    pub max_speed: u32,
+    /// Acceleration and deceleration, in steps/s²
+    pub acceleration: u32,
+}
+
+impl Config {
+    pub fn tick_rate<T: Into<Hertz>>(mut self, tick_rate: T) -> Self {
+        self.tick_rate = tick_rate.into();
+        self
+    }
+
+    pub fn pulse_width<T: Into<MicroSeconds>>(mut self, pulse_width: T) -> Self {
+        self.pulse_width = pulse_width.into();
+        self
+    }
+
+    pub fn max_speed(mut self, steps_per_second: u32) -> Self {
+        self.max_speed = steps_per_second;
+        self
+    }
+
+    pub fn acceleration(mut self, steps_per_second2: u32) -> Self {
+        self.acceleration = steps_per_second2;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            tick_rate: Hertz(1_000_000),
+            pulse_width: MicroSeconds(5),
+            max_speed: 1_000,
+            acceleration: 1_000,
+        }
+    }
+}
+
+/// DMA target writing the step intervals to the auto-reload register on each update event
+///
+/// Created by [`Stepper::prepare_dma`], it is used with the stream of the `TIMx_UP` DMA request.
+pub struct ArrDma<TIM> {
+    _tim: PhantomData<TIM>,
+}
+
+/// Step/dir stepper motor driven by a timer
+pub struct Stepper<TIM, C, DIR> {
+    step: PwmChannels<TIM, C>,
+    dir: DIR,
+    config: Config,
+    tick_rate: Hertz,
+    pulse: u32,
+    ramp: Ramp,
+    direction: i64,
+    position: i64,
+}
+
+macro_rules! stepper {
+    ($($TIMX:ident: $bits:ty,)+) => {
+        $(
+            impl<C, DIR> Stepper<$TIMX, C, DIR>
+            where
+                PwmChannels<$TIMX, C>: PwmPin<Duty = $bits>,
+                DIR: OutputPin<Error = Infallible>,
+            {
+                /// Drives a stepper motor with the step pulses on the PWM channel `step` and the
+                /// direction on `dir`, high for positive moves.
+                ///
+                /// Returns `timer::Error::TickRate` if the tick rate cannot be derived from the
+                /// timer clock.
+                pub fn new(
+                    mut step: PwmChannels<$TIMX, C>,
+                    dir: DIR,
+                    config: Config,
+                    clocks: &Clocks,
+                ) -> Result<Self, Error> {
+                    let tim = unsafe { &*<$TIMX>::ptr() };
+                    let clk = <$TIMX>::get_timer_frequency(clocks).0;
+                    let psc = timer::prescaler(Hertz(clk), config.tick_rate)?;
+
+                    tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    tim.psc.write(|w| w.psc().bits(psc));
+                    step.set_duty(0);
+                    // Only the counter overflows, the steps, generate update interrupts
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+                    tim.egr.write(|w| w.ug().set_bit());
+                    step.enable();
+
+                    let tick_rate = Hertz(clk / (u32::from(psc) + 1));
+                    let pulse = u64::from(tick_rate.0) * u64::from(config.pulse_width.0) / 1_000_000;
+                    Ok(Stepper {
+                        step,
+                        dir,
+                        config,
+                        tick_rate,
+                        pulse: pulse.max(1) as u32,
+                        ramp: Ramp::new(0, tick_rate, config.max_speed, config.acceleration),
+                        direction: 0,
+                        position: 0,
+                    })
+                }
+
+                /// Returns the counter frequency
+                pub fn tick_rate(&self) -> Hertz {
+                    self.tick_rate
+                }
+
+                /// Sets the maximum speed and the acceleration of the next moves
+                pub fn set_profile(&mut self, max_speed: u32, acceleration: u32) {
+                    self.config.max_speed = max_speed;
+                    self.config.acceleration = acceleration;
+                }
+
+                /// Starts a move of `steps`, in the negative direction if negative, driven by the
+                /// update interrupt. [`on_interrupt`](Self::on_interrupt) must be called from the
+                /// timer interrupt.
+                pub fn move_by(&mut self, steps: i32) {
+                    if self.prepare(steps) {
+                        unsafe { (*<$TIMX>::ptr()).dier.modify(|_, w| w.uie().set_bit()) }
+                        self.start();
+                    }
+                }
+
+                /// Starts a move to the absolute `position`, driven by the update interrupt
+                pub fn move_to(&mut self, position: i64) {
+                    let steps = (position - self.position).max(i64::from(i32::MIN)).min(i64::from(i32::MAX));
+                    self.move_by(steps as i32);
+                }
+
+                /// Writes the interval of the next step, call this from the timer interrupt
+                pub fn on_interrupt(&mut self) {
+                    let tim = unsafe { &*<$TIMX>::ptr() };
+                    if tim.sr.read().uif().bit_is_clear() {
+                        return;
+                    }
+                    // NOTE(unsafe) writing 1 to the other flags has no effect
+                    tim.sr.write(|w| unsafe { w.bits(<$TIMX as Flags>::FLAGS & !UIF) });
+                    if self.direction == 0 {
+                        return;
+                    }
+                    self.position += self.direction;
+                    if tim.cr1.read().cen().bit_is_clear() {
+                        // The last step is done
+                        self.direction = 0;
+                        tim.dier.modify(|_, w| w.uie().clear_bit());
+                    } else {
+                        self.preload_next();
+                    }
+                }
+
+                /// Prepares a move of `steps` with the intervals written by DMA, and returns the
+                /// number of auto-reload values written to `buf` to transfer with the returned
+                /// target. The
+                /// move then starts with [`start`](Self::start), after the DMA transfer.
+                ///
+                /// [`finish_dma`](Self::finish_dma) must be called from the DMA transfer complete
+                /// interrupt to stop after the last step. The position is the target of the move
+                /// right away. A move of a single step needs no transfer and stops by itself.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `buf` holds less than `steps - 1` intervals.
+                pub fn prepare_dma(&mut self, steps: i32, buf: &mut [$bits]) -> (usize, ArrDma<$TIMX>) {
+                    let count = (steps.unsigned_abs() as usize).saturating_sub(1);
+                    assert!(buf.len() >= count);
+                    if self.prepare(steps) {
+                        // The request at the start of the last step writes the last entry, which
+                        // is not used: the transfer completes while the last step runs
+                        let max = <$bits>::MAX as u32;
+                        let mut last = max;
+                        for slot in buf[..count].iter_mut() {
+                            last = self
+                                .ramp
+                                .next()
+                                .map_or(last, |interval| auto_reload(interval, max));
+                            *slot = last as $bits;
+                        }
+                        self.position += i64::from(steps);
+                        self.direction = 0;
+                    }
+                    if count > 0 {
+                        let tim = unsafe { &*<$TIMX>::ptr() };
+                        // DMA burst of 1 transfer to ARR, at offset 11 words
+                        tim.dcr.write(|w| unsafe { w.dba().bits(11).dbl().bits(0) });
+                        tim.dier.modify(|_, w| w.ude().set_bit());
+                    }
+                    (count, ArrDma { _tim: PhantomData })
+                }
+
+                /// Stops after the step running, call this from the DMA transfer complete
+                /// interrupt of a move started with [`prepare_dma`](Self::prepare_dma)
+                pub fn finish_dma(&mut self) {
+                    unsafe { (*<$TIMX>::ptr()).dier.modify(|_, w| w.ude().clear_bit()) }
+                    self.stop_after_step();
+                }
+
+                /// Starts the prepared move
+                pub fn start(&mut self) {
+                    let tim = unsafe { &*<$TIMX>::ptr() };
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+                }
+
+                /// Stops right away, the position may be off by the step running
+                ///
+                /// A step pulse in progress is cut short, the step output goes inactive.
+                pub fn stop(&mut self) {
+                    let tim = unsafe { &*<$TIMX>::ptr() };
+                    tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    tim.dier.modify(|_, w| w.uie().clear_bit().ude().clear_bit());
+                    // Load the zero duty and reset the counter, which turns the output inactive.
+                    // URS is set, so the update event sets no flag
+                    self.step.set_duty(0);
+                    tim.egr.write(|w| w.ug().set_bit());
+                    // NOTE(unsafe) writing 1 to the other flags has no effect
+                    tim.sr.write(|w| unsafe { w.bits(<$TIMX as Flags>::FLAGS & !UIF) });
+                    self.direction = 0;
+                }
+
+                /// Returns `true` while the motor is moving
+                pub fn is_moving(&self) -> bool {
+                    unsafe { (*<$TIMX>::ptr()).cr1.read().cen().bit_is_set() }
+                }
+
+                /// Returns the position in steps
+                pub fn position(&self) -> i64 {
+                    self.position
+                }
+
+                /// Sets the current position
+                pub fn set_position(&mut self, position: i64) {
+                    self.position = position;
+                }
+
+                /// Stops and releases the PWM channel and the direction pin
+                pub fn release(mut self) -> (PwmChannels<$TIMX, C>, DIR) {
+                    self.stop();
+                    (self.step, self.dir)
+                }
+
+                // Loads the first step interval and preloads the second one
+                fn prepare(&mut self, steps: i32) -> bool {
+                    self.stop();
+                    if steps == 0 {
+                        return false;
+                    }
+                    if steps > 0 {
+                        self.dir.set_high().ok();
+                        self.direction = 1;
+                    } else {
+                        self.dir.set_low().ok();
+                        self.direction = -1;
+                    }
+                    let tim = unsafe { &*<$TIMX>::ptr() };
+                    self.ramp = Ramp::new(
+                        steps.unsigned_abs(),
+                        self.tick_rate,
+                        self.config.max_speed,
+                        self.config.acceleration,
+                    );
+                    tim.cr1.modify(|_, w| w.opm().clear_bit());
+                    let max = <$bits>::MAX as u32;
+                    let first = self.ramp.next().map_or(1, |interval| auto_reload(interval, max));
+                    tim.arr.write(|w| unsafe { w.bits(first) });
+                    self.step.set_duty(self.pulse.min(first) as $bits);
+                    tim.egr.write(|w| w.ug().set_bit());
+                    self.preload_next();
+                    true
+                }
+
+                // Preloads the interval of the step after the running one, or stops after the
+                // running one if it is the last
+                fn preload_next(&mut self) {
+                    match self.ramp.next() {
+                        Some(interval) => {
+                            let arr = auto_reload(interval, <$bits>::MAX as u32);
+                            unsafe { (*<$TIMX>::ptr()).arr.write(|w| w.bits(arr)) }
+                        }
+                        None => self.stop_after_step(),
+                    }
+                }
+
+                fn stop_after_step(&mut self) {
+                    // No pulse once stopped, the counter stops at the next update event
+                    self.step.set_duty(0);
+                    unsafe { (*<$TIMX>::ptr()).cr1.modify(|_, w| w.opm().set_bit()) }
+                }
+            }
+
+            impl ArrDma<$TIMX> {
+                /// Stops the DMA requests on update events
+                pub fn release(self) {
+                    unsafe { (*<$TIMX>::ptr()).dier.modify(|_, w| w.ude().clear_bit()) }
+                }
+            }
+
+            unsafe impl PeriAddress for ArrDma<$TIMX> {
+                #[inline(always)]
+                fn address(&self) -> u32 {
+                    unsafe { &(*<$TIMX>::ptr()).dmar as *const _ as u32 }
+                }
+
+                type MemSize = $bits;
+            }
+
+            /// DMA is available for the intervals based on the mappings for the DMA burst
+            /// register
+            unsafe impl<STREAM, const CHANNEL: u8> DMASet<STREAM, MemoryToPeripheral, CHANNEL>
+                for ArrDma<$TIMX>
+            where
+                DMAR<$TIMX>: DMASet<STREAM, MemoryToPeripheral, CHANNEL>,
+            {
+            }
+        )+
+    };
+}
+
+stepper!(TIM1: u16, TIM5: u32,);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+stepper!(TIM2: u32, TIM3: u16, TIM4: u16,);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+stepper!(TIM8: u16,);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MHZ: Hertz = Hertz(1_000_000);
+
+    fn intervals<const N: usize>(ramp: Ramp) -> [u32; N] {
+        let mut out = [0; N];
+        let mut count = 0;
+        for (slot, interval) in out.iter_mut().zip(ramp) {
+            *slot = interval;
+            count += 1;
+        }
+        assert_eq!(count, N);
+        out
+    }
+
+    #[test]
+    fn isqrt_rounds_down() {
+        for &value in &[0, 1, 2, 3, 4, 15, 16, 17, 1 << 40, (1 << 40) - 1, u64::MAX] {
+            let root = isqrt(value);
+            assert!(root * root <= value);
+            assert!((root + 1)
+                .checked_mul(root + 1)
+                .map_or(true, |sq| sq > value));
+        }
+        assert_eq!(isqrt(1 << 48), 1 << 24);
+    }
+
+    #[test]
+    fn step_count() {
+        for &steps in &[0, 1, 2, 3, 10, 200, 5_000] {
+            let mut ramp = Ramp::new(steps, MHZ, 1_000, 1_000);
+            assert_eq!(ramp.remaining(), steps);
+            assert_eq!(ramp.size_hint(), (steps as usize, Some(steps as usize)));
+            ramp.next();
+            assert_eq!(ramp.remaining(), steps.saturating_sub(1));
+            assert_eq!(ramp.count() as u32, steps.saturating_sub(1));
+        }
+    }
+
+    // The deceleration reverts the acceleration steps, equal up to the rounding of the intervals
+    fn assert_symmetric(steps: &[u32]) {
+        for (accel, decel) in steps.iter().zip(steps.iter().rev()) {
+            assert!((i64::from(*accel) - i64::from(*decel)).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn acceleration_and_deceleration_are_symmetric() {
+        let steps: [u32; 200] = intervals(Ramp::new(200, MHZ, 1_000, 5_000));
+        assert_eq!(steps[0], 13519);
+        assert_symmetric(&steps);
+        // Faster on each step up to the middle of the move
+        for pair in steps[..100].windows(2) {
+            assert!(pair[1] < pair[0]);
+        }
+    }
+
+    #[test]
+    fn speed_is_capped() {
+        let steps: [u32; 5_000] = intervals(Ramp::new(5_000, MHZ, 1_000, 5_000));
+        // 1000 steps/s at 1 MHz
+        assert_eq!(steps.iter().min(), Some(&1_000));
+        let cruise = steps.iter().filter(|&&interval| interval == 1_000).count();
+        assert!(cruise > 4_000);
+        assert_symmetric(&steps);
+
+        // Already at the maximum speed on the first step
+        let steps: [u32; 5] = intervals(Ramp::new(5, MHZ, 10, 1_000));
+        assert_eq!(steps, [100_000; 5]);
+    }
+
+    #[test]
+    fn short_move_is_triangular() {
+        // Too short to reach the maximum speed: it decelerates right after accelerating
+        assert_eq!(
+            intervals(Ramp::new(3, MHZ, 100, 100)),
+            [95600, 57360, 95600]
+        );
+        assert_eq!(
+            intervals(Ramp::new(4, MHZ, 1_000, 1_000)),
+            [30231, 18138, 18138, 30231]
+        );
+        let steps: [u32; 10] = intervals(Ramp::new(10, MHZ, 1_000, 1_000));
+        assert!(steps.iter().all(|&interval| interval > 1_000));
+        assert_eq!(steps.iter().min(), Some(&10533));
+        assert_symmetric(&steps);
+    }
+}
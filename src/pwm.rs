@@ -1,6 +1,16 @@
-use crate::{bb, hal as pwm, time::Hertz, timer::Timer};
-use cast::{u16, u32};
-use core::{marker::PhantomData, mem::MaybeUninit};
+use crate::dma::traits::{DMASet, PeriAddress, DMAR};
+use crate::dma::MemoryToPeripheral;
+use crate::rcc::{Clocks, GetBusFreq};
+use crate::{
+    bb, hal as pwm,
+    time::{Hertz, Period},
+    timer::{self, Timer},
+};
+use core::{
+    marker::PhantomData,
+    mem::{self, MaybeUninit},
+    ops::{Deref, DerefMut},
+};
 
 use crate::pac::{TIM1, TIM11, TIM5, TIM9};
 
@@ -53,17 +63,221 @@ use crate::timer::PinC1;
 use crate::timer::PinC2;
 use crate::timer::PinC3;
 use crate::timer::PinC4;
+use crate::timer::{Event, Flags, InternalTrigger, PinBkin, PinC1N, PinC2N, PinC3N};
+
+/// Commutation interrupt flag
+const COMIF: u32 = 1 << 5;
+
+/// PWM error
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Error {
+    /// The dead time is longer than the dead-time generator allows
+    DeadTime,
+    /// The frequency can not be reached with the timer clock
+    Frequency,
+    /// The one-pulse width is 0, or the delay and width do not fit in the counter
+    Pulse,
+}
+
+/// Counter alignment
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Alignment {
+    /// Edge-aligned, the counter counts up (default)
+    Edge = 0,
+    /// Center-aligned, compare interrupts while counting down
+    Center1 = 1,
+    /// Center-aligned, compare interrupts while counting up
+    Center2 = 2,
+    /// Center-aligned, compare interrupts while counting up and down
+    Center3 = 3,
+}
 
 pub struct C1;
 pub struct C2;
 pub struct C3;
 pub struct C4;
 
+/// Output compare mode, what the channel output does when the counter matches the duty
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Mode {
+    /// The output keeps its level
+    Frozen = 0,
+    /// The output is set active on match
+    ActiveOnMatch = 1,
+    /// The output is set inactive on match
+    InactiveOnMatch = 2,
+    /// The output toggles on match
+    Toggle = 3,
+    /// The output is forced inactive
+    ForceInactive = 4,
+    /// The output is forced active
+    ForceActive = 5,
+    /// The output is active while the counter is below the duty (default)
+    PwmMode1 = 6,
+    /// The output is inactive while the counter is below the duty
+    PwmMode2 = 7,
+}
+
+/// Level of an active output
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Polarity {
+    /// Active high (default)
+    ActiveHigh,
+    /// Active low
+    ActiveLow,
+}
+
+/// Protection of the timer configuration against software errors, on TIM1 and TIM8
+///
+/// Once set, the lock level can only be changed by a reset.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum LockLevel {
+    /// No protection (default)
+    Off = 0,
+    /// Dead time, break and idle states are locked
+    Level1 = 1,
+    /// Level 1 and the channel polarities are locked
+    Level2 = 2,
+    /// Level 2 and the output compare modes are locked
+    Level3 = 3,
+}
+
+/// Start of the pulse in one-pulse mode
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Trigger {
+    /// Started by software with `start_pulse`
+    Software,
+    /// Started by a rising edge on the channel 1 input, channel 1 can not be used as an output
+    Ti1,
+    /// Started by a rising edge on the channel 2 input, channel 2 can not be used as an output
+    Ti2,
+    /// Started by a rising edge on the external trigger input
+    Etr,
+}
+
+/// Output level while the main outputs are disabled, on TIM1 and TIM8
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum IdleState {
+    /// Low (default)
+    Reset,
+    /// High
+    Set,
+}
+
 pub struct PwmChannels<TIM, CHANNELS> {
     _channel: PhantomData<CHANNELS>,
     _tim: PhantomData<TIM>,
 }
 
+/// Commit of a synchronized update
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Commit {
+    /// The new values apply together at the end of the running period
+    NextPeriod,
+    /// The new values apply together right away with a software update event, the counter
+    /// restarts a period
+    Immediate,
+}
+
+/// Synchronized update of the channels of a timer
+///
+/// Created by `begin_update` on the tuple of channels returned by `pwm`, see [`UpdateExt`]: the
+/// update events are disabled (UDIS) until [`commit`](Update::commit), so the duties and the
+/// period set in the meantime, held in the preload registers, apply together instead of some of
+/// them a period later. The duties must be preloaded, which is the default, see `set_preload`. No
+/// update interrupt or DMA request happens during the update.
+///
+/// The channels it was created from are borrowed and reached through it. Dropping it without
+/// `commit` applies the new values at the end of the running period, like `Commit::NextPeriod`.
+#[must_use = "the update events stay disabled until the update is committed or dropped"]
+pub struct Update<'a, CH: UpdateExt> {
+    channels: &'a mut CH,
+}
+
+impl<CH: UpdateExt> Update<'_, CH> {
+    /// Applies the duties and the period set since `begin_update` together
+    pub fn commit(self, commit: Commit) {
+        <CH::Timer as sealed::UpdateDisable>::end_update(commit == Commit::Immediate);
+        mem::forget(self);
+    }
+}
+
+impl<CH: UpdateExt> Deref for Update<'_, CH> {
+    type Target = CH;
+
+    fn deref(&self) -> &Self::Target {
+        self.channels
+    }
+}
+
+impl<CH: UpdateExt> DerefMut for Update<'_, CH> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.channels
+    }
+}
+
+impl<CH: UpdateExt> Drop for Update<'_, CH> {
+    fn drop(&mut self) {
+        <CH::Timer as sealed::UpdateDisable>::end_update(false);
+    }
+}
+
+/// Synchronized updates of the channels of a timer
+///
+/// It is implemented for the tuples of two channels or more returned by `pwm`. Borrowing the
+/// whole tuple, a single update of the timer can run at a time.
+pub trait UpdateExt: Sized {
+    /// Timer of the channels
+    type Timer: sealed::UpdateDisable;
+
+    /// Starts a synchronized update of the channels
+    fn begin_update(&mut self) -> Update<'_, Self> {
+        <Self::Timer as sealed::UpdateDisable>::begin_update();
+        Update { channels: self }
+    }
+}
+
+macro_rules! update_ext {
+    ( $( ( $($ENCHX:ident),+ ); )+ ) => {
+        $(
+            impl<TIM: sealed::UpdateDisable> UpdateExt for ($(PwmChannels<TIM, $ENCHX>),+) {
+                type Timer = TIM;
+            }
+        )+
+    };
+}
+
+update_ext!(
+    (C1, C2, C3, C4);
+    (C2, C3, C4);
+    (C1, C3, C4);
+    (C1, C2, C4);
+    (C1, C2, C3);
+    (C3, C4);
+    (C2, C4);
+    (C2, C3);
+    (C1, C4);
+    (C1, C3);
+    (C1, C2);
+);
+
+mod sealed {
+    pub trait UpdateDisable {
+        /// Disables the update events
+        fn begin_update();
+        /// Enables the update events, and generates one to load the registers if `immediate`
+        fn end_update(immediate: bool);
+    }
+}
+
+/// DMA target writing duties to the channels of a timer on each update event
+///
+/// Created by `dma_duties` on a PWM channel, it is used with the stream of the `TIMx_UP` DMA
+/// request.
+pub struct DutyDma<TIM> {
+    _tim: PhantomData<TIM>,
+}
+
 macro_rules! pins_impl {
     ( $( ( $($PINX:ident),+ ), ( $($TRAIT:ident),+ ), ( $($ENCHX:ident),* ); )+ ) => {
         $(
@@ -134,9 +348,55 @@ macro_rules! brk {
     ($_other:ident, $_tim:ident) => {};
 }
 
+macro_rules! pwm_events {
+    ($TIMX:ty, $bit:literal) => {
+        /// Starts listening for the compare match interrupt of the channel
+        #[allow(unused_unsafe)]
+        #[inline]
+        pub fn listen(&mut self) {
+            let mask = Event::C1.mask() << ($bit / 4);
+            unsafe {
+                (*<$TIMX>::ptr())
+                    .dier
+                    .modify(|r, w| w.bits(r.bits() | mask))
+            }
+        }
+
+        /// Stops listening for the compare match interrupt of the channel
+        #[allow(unused_unsafe)]
+        #[inline]
+        pub fn unlisten(&mut self) {
+            let mask = Event::C1.mask() << ($bit / 4);
+            unsafe {
+                (*<$TIMX>::ptr())
+                    .dier
+                    .modify(|r, w| w.bits(r.bits() & !mask))
+            }
+        }
+
+        /// Clears the compare match interrupt flag of the channel
+        #[allow(unused_unsafe)]
+        #[inline]
+        pub fn clear_interrupt(&mut self) {
+            // Writing 1 leaves the other flags unchanged
+            let flags = <$TIMX as Flags>::FLAGS & !(Event::C1.mask() << ($bit / 4));
+            unsafe { (*<$TIMX>::ptr()).sr.write(|w| w.bits(flags)) }
+        }
+
+        /// Returns `true` if the compare match interrupt flag of the channel is set
+        #[inline]
+        pub fn is_pending(&self) -> bool {
+            let mask = Event::C1.mask() << ($bit / 4);
+            unsafe { (*<$TIMX>::ptr()).sr.read().bits() & mask != 0 }
+        }
+    };
+}
+
 macro_rules! pwm_pin {
-    ($TIMX:ty, $C:ty, $ccr: ident, $bit:literal) => {
+    ($TIMX:ty, $C:ty, $ccr: ident, $bit:literal, $bits:ty) => {
         impl PwmChannels<$TIMX, $C> {
+            pwm_events!($TIMX, $bit);
+
             //NOTE(unsafe) atomic write with no side effects
             #[inline]
             pub fn disable(&mut self) {
@@ -151,25 +411,25 @@ macro_rules! pwm_pin {
 
             //NOTE(unsafe) atomic read with no side effects
             #[inline]
-            pub fn get_duty(&self) -> u16 {
-                unsafe { (*<$TIMX>::ptr()).$ccr.read().ccr().bits() as u16 }
+            pub fn get_duty(&self) -> $bits {
+                unsafe { (*<$TIMX>::ptr()).$ccr.read().ccr().bits() as $bits }
             }
 
             //NOTE(unsafe) atomic read with no side effects
             #[inline]
-            pub fn get_max_duty(&self) -> u16 {
-                unsafe { (*<$TIMX>::ptr()).arr.read().arr().bits() as u16 }
+            pub fn get_max_duty(&self) -> $bits {
+                unsafe { (*<$TIMX>::ptr()).arr.read().arr().bits() as $bits }
             }
 
             //NOTE(unsafe) atomic write with no side effects
             #[inline]
-            pub fn set_duty(&mut self, duty: u16) {
+            pub fn set_duty(&mut self, duty: $bits) {
                 unsafe { (*<$TIMX>::ptr()).$ccr.write(|w| w.ccr().bits(duty.into())) }
             }
         }
 
         impl pwm::PwmPin for PwmChannels<$TIMX, $C> {
-            type Duty = u16;
+            type Duty = $bits;
             fn disable(&mut self) {
                 self.disable()
             }
@@ -189,15 +449,632 @@ macro_rules! pwm_pin {
     };
 }
 
+macro_rules! output_compare {
+    ($TIMX:ty, $C:ty, $ccmr_output:ident, $ocm:ident, $ocpe:ident, $ccp_bit:literal) => {
+        impl PwmChannels<$TIMX, $C> {
+            /// Set the output compare mode
+            #[allow(unused_unsafe)]
+            #[inline]
+            pub fn set_mode(&mut self, mode: Mode) {
+                //NOTE(unsafe) all the `Mode` values are valid values to write to ocxm
+                unsafe {
+                    (*<$TIMX>::ptr())
+                        .$ccmr_output()
+                        .modify(|_, w| w.$ocm().bits(mode as u8))
+                }
+            }
+
+            /// Enable/disable the preload of the duty, when disabled a new duty applies
+            /// immediately instead of at the next update event
+            #[inline]
+            pub fn set_preload(&mut self, enable: bool) {
+                unsafe {
+                    (*<$TIMX>::ptr())
+                        .$ccmr_output()
+                        .modify(|_, w| w.$ocpe().bit(enable))
+                }
+            }
+
+            //NOTE(unsafe) atomic write with no side effects
+            /// Set the output polarity
+            #[inline]
+            pub fn set_polarity(&mut self, polarity: Polarity) {
+                unsafe {
+                    bb::write(
+                        &(*<$TIMX>::ptr()).ccer,
+                        $ccp_bit,
+                        polarity == Polarity::ActiveLow,
+                    )
+                }
+            }
+        }
+    };
+}
+
+macro_rules! idle_state {
+    ($TIMX:ty: $($C:ty: $ois_bit:literal),+) => {
+        $(
+        impl PwmChannels<$TIMX, $C> {
+            //NOTE(unsafe) atomic write with no side effects
+            /// Set the output level while the main outputs are disabled
+            #[inline]
+            pub fn set_idle_state(&mut self, state: IdleState) {
+                unsafe {
+                    bb::write(
+                        &(*<$TIMX>::ptr()).cr2,
+                        $ois_bit,
+                        state == IdleState::Set,
+                    )
+                }
+            }
+        }
+        )+
+    };
+}
+
+/// Computes the prescaler and auto-reload values for a PWM period of `ticks` timer clock cycles.
+///
+/// In center-aligned mode the counter counts up and down during one period.
+/// `max_arr` is the largest auto-reload value of the timer.
+fn prescaler_for_ticks(ticks: u32, center: bool, max_arr: u32) -> Result<(u16, u32), Error> {
+    let ticks = if center { ticks / 2 } else { ticks };
+    if ticks < 2 {
+        return Err(Error::Frequency);
+    }
+    let psc = ((u64::from(ticks) - 1) / (u64::from(max_arr) + 1)) as u32;
+    if psc > 0xffff {
+        return Err(Error::Frequency);
+    }
+    let arr = ticks / (psc + 1);
+    // Edge-aligned the period is ARR + 1 ticks, center-aligned it is 2 * ARR ticks
+    let arr = if center { arr } else { arr - 1 };
+    Ok((psc as u16, arr))
+}
+
+/// Computes the prescaler and auto-reload values for an edge-aligned PWM at `freq`.
+fn prescaler(clk: Hertz, freq: Hertz, max_arr: u32) -> Result<(u16, u32), Error> {
+    let ticks = clk.0.checked_div(freq.0).ok_or(Error::Frequency)?;
+    prescaler_for_ticks(ticks, false, max_arr)
+}
+
+macro_rules! pwm_frequency {
+    ($TIMX:ty, $bits:ty $(, $cms:ident)?) => {
+        impl<C> PwmChannels<$TIMX, C> {
+            /// Set the PWM frequency of all the channels of the timer.
+            ///
+            /// The new frequency applies from the next update event. The duties are not scaled,
+            /// they should be set again relative to `get_max_duty`.
+            pub fn set_frequency<T: Into<Hertz>>(&mut self, freq: T, clocks: &Clocks) -> Result<(), Error> {
+                let clk = <$TIMX>::get_timer_frequency(clocks).0;
+                let ticks = clk.checked_div(freq.into().0).ok_or(Error::Frequency)?;
+                self.set_period(ticks)
+            }
+
+            /// Set the PWM period of all the channels of the timer, in timer clock cycles.
+            ///
+            /// The new period applies from the next update event. The duties are not scaled,
+            /// they should be set again relative to `get_max_duty`.
+            pub fn set_period(&mut self, ticks: u32) -> Result<(), Error> {
+                let tim = unsafe { &*<$TIMX>::ptr() };
+                let center = false $(|| tim.cr1.read().$cms().bits() != 0)?;
+                let (psc, arr) = prescaler_for_ticks(ticks, center, <$bits>::MAX as u32)?;
+                // Both registers are preloaded
+                tim.psc.write(|w| w.psc().bits(psc));
+                tim.arr.write(|w| unsafe { w.bits(arr) });
+                Ok(())
+            }
+
+            /// Returns the PWM period of all the channels of the timer
+            pub fn get_period(&self, clocks: &Clocks) -> Period {
+                let tim = unsafe { &*<$TIMX>::ptr() };
+                let clk = u64::from(<$TIMX>::get_timer_frequency(clocks).0);
+                let psc = u64::from(tim.psc.read().psc().bits()) + 1;
+                let arr = u64::from(tim.arr.read().bits());
+                let center = false $(|| tim.cr1.read().$cms().bits() != 0)?;
+                // Edge-aligned the period is ARR + 1 ticks, center-aligned it is 2 * ARR ticks
+                let ticks = if center { 2 * arr } else { arr + 1 } * psc;
+                // Split to not overflow, the remainder is below the clock
+                Period::Nanos(ticks / clk * 1_000_000_000 + ticks % clk * 1_000_000_000 / clk)
+            }
+        }
+    };
+}
+
+macro_rules! pwm_update {
+    ($TIMX:ty) => {
+        impl sealed::UpdateDisable for $TIMX {
+            fn begin_update() {
+                //NOTE(unsafe) atomic write with no side effects
+                unsafe { (*<$TIMX>::ptr()).cr1.modify(|_, w| w.udis().set_bit()) }
+            }
+
+            fn end_update(immediate: bool) {
+                let tim = unsafe { &*<$TIMX>::ptr() };
+                tim.cr1.modify(|_, w| w.udis().clear_bit());
+                if immediate {
+                    // Only load the registers, without update interrupt
+                    let urs = tim.cr1.read().urs().bit_is_set();
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+                    tim.egr.write(|w| w.ug().set_bit());
+                    tim.cr1.modify(|_, w| w.urs().bit(urs));
+                }
+            }
+        }
+    };
+}
+
+macro_rules! pwm_alignment {
+    ($TIMX:ty) => {
+        impl<C> PwmChannels<$TIMX, C> {
+            /// Set the counter alignment of the timer.
+            ///
+            /// The counter is stopped during the change, then restarted if it was running.
+            /// Center-aligned the counter counts up and down, use `set_frequency` afterwards to
+            /// keep the frequency.
+            #[allow(unused_unsafe)]
+            pub fn set_alignment(&mut self, alignment: Alignment) {
+                let tim = unsafe { &*<$TIMX>::ptr() };
+                let enabled = tim.cr1.read().cen().bit_is_set();
+                tim.cr1.modify(|_, w| w.cen().clear_bit());
+                tim.cr1
+                    .modify(|_, w| unsafe { w.dir().clear_bit().cms().bits(alignment as u8) });
+                tim.cr1.modify(|_, w| w.cen().bit(enabled));
+            }
+        }
+    };
+}
+
+/// Finds the clock division and the dead-time generator setup for a dead time of at least `ticks`
+/// timer clock cycles.
+fn dead_time_bits(ticks: u32) -> Option<(u8, u8)> {
+    for ckd in 0..3 {
+        let div = 1 << ckd;
+        let dts = (ticks + div - 1) / div;
+        let dtg = match dts {
+            0..=127 => dts,
+            128..=254 => 0b1000_0000 | ((dts + 1) / 2 - 64),
+            255..=504 => 0b1100_0000 | ((dts + 7) / 8 - 32),
+            505..=1008 => 0b1110_0000 | ((dts + 15) / 16 - 32),
+            _ => continue,
+        };
+        return Some((ckd as u8, dtg as u8));
+    }
+    None
+}
+
+macro_rules! complementary_pin {
+    ($TIMX:ty, $C:ty, $PINCN:ident, $ccne_bit:literal, $ccnp_bit:literal, $oisn_bit:literal) => {
+        impl PwmChannels<$TIMX, $C> {
+            /// Drive `pin` with the complementary output of the channel and enable it
+            pub fn set_complementary_pin<NPIN: $PINCN<$TIMX>>(&mut self, _pin: NPIN) {
+                self.enable_complementary()
+            }
+
+            //NOTE(unsafe) atomic write with no side effects
+            #[inline]
+            pub fn disable_complementary(&mut self) {
+                unsafe { bb::clear(&(*<$TIMX>::ptr()).ccer, $ccne_bit) }
+            }
+
+            //NOTE(unsafe) atomic write with no side effects
+            #[inline]
+            pub fn enable_complementary(&mut self) {
+                unsafe { bb::set(&(*<$TIMX>::ptr()).ccer, $ccne_bit) }
+            }
+
+            //NOTE(unsafe) atomic write with no side effects
+            /// Set the complementary output polarity
+            #[inline]
+            pub fn set_complementary_polarity(&mut self, polarity: Polarity) {
+                unsafe {
+                    bb::write(
+                        &(*<$TIMX>::ptr()).ccer,
+                        $ccnp_bit,
+                        polarity == Polarity::ActiveLow,
+                    )
+                }
+            }
+
+            //NOTE(unsafe) atomic write with no side effects
+            /// Set the complementary output level while the main outputs are disabled
+            #[inline]
+            pub fn set_complementary_idle_state(&mut self, state: IdleState) {
+                unsafe { bb::write(&(*<$TIMX>::ptr()).cr2, $oisn_bit, state == IdleState::Set) }
+            }
+        }
+    };
+}
+
+macro_rules! advanced_timer {
+    ($($TIMX:ident,)+) => {
+        $(
+            impl<C> PwmChannels<$TIMX, C> {
+                /// Set the dead time inserted between a channel output and its complementary
+                /// output, for all the channels of the timer.
+                ///
+                /// The dead time is rounded up to the resolution of the dead-time generator. Long
+                /// dead times need a division of the clock, which also slows down the sampling of
+                /// the input filters.
+                pub fn set_dead_time(&mut self, dead_time_ns: u32, clocks: &Clocks) -> Result<(), Error> {
+                    let clk = <$TIMX>::get_timer_frequency(clocks).0;
+                    let ticks = (u64::from(dead_time_ns) * u64::from(clk) + 999_999_999) / 1_000_000_000;
+                    let (ckd, dtg) = if ticks > u64::from(u32::MAX) {
+                        None
+                    } else {
+                        dead_time_bits(ticks as u32)
+                    }
+                    .ok_or(Error::DeadTime)?;
+
+                    let tim = unsafe { &*<$TIMX>::ptr() };
+                    tim.cr1.modify(|_, w| unsafe { w.ckd().bits(ckd) });
+                    tim.bdtr.modify(|_, w| unsafe { w.dtg().bits(dtg) });
+                    Ok(())
+                }
+
+                /// Enable the break input on `pin`, disabling all the outputs of the timer while
+                /// the input is active.
+                ///
+                /// The STM32F4 timers have a single break input.
+                pub fn enable_break<PIN: PinBkin<$TIMX>>(&mut self, _pin: PIN, polarity: Polarity) {
+                    unsafe {
+                        (*<$TIMX>::ptr()).bdtr.modify(|_, w| {
+                            w.bke()
+                                .set_bit()
+                                .bkp()
+                                .bit(polarity == Polarity::ActiveHigh)
+                        })
+                    }
+                }
+
+                /// Disable the break input
+                pub fn disable_break(&mut self) {
+                    unsafe { (*<$TIMX>::ptr()).bdtr.modify(|_, w| w.bke().clear_bit()) }
+                }
+
+                /// Enable/disable the automatic enabling of the outputs at the next update event
+                /// once the break input is inactive, enabled by `Timer::pwm`
+                pub fn set_automatic_output_enable(&mut self, enable: bool) {
+                    unsafe { (*<$TIMX>::ptr()).bdtr.modify(|_, w| w.aoe().bit(enable)) }
+                }
+
+                /// Enable the outputs of the timer, after a break when the automatic output enable
+                /// is disabled
+                pub fn enable_outputs(&mut self) {
+                    unsafe { (*<$TIMX>::ptr()).bdtr.modify(|_, w| w.moe().set_bit()) }
+                }
+
+                /// Returns `true` if the outputs are enabled, `false` after a break
+                pub fn outputs_enabled(&self) -> bool {
+                    unsafe { (*<$TIMX>::ptr()).bdtr.read().moe().bit_is_set() }
+                }
+
+                /// Set the lock level, it can only be changed again by a reset
+                pub fn set_lock_level(&mut self, level: LockLevel) {
+                    unsafe {
+                        (*<$TIMX>::ptr())
+                            .bdtr
+                            .modify(|_, w| w.lock().bits(level as u8))
+                    }
+                }
+
+                /// Preload the channel enables and output modes, and transfer them together on a
+                /// commutation event triggered by [`commutate`](Self::commutate).
+                ///
+                /// This is used for six-step control of brushless motors, the next step is set on
+                /// the channels while the current one is output.
+                pub fn enable_commutation(&mut self) {
+                    unsafe {
+                        (*<$TIMX>::ptr())
+                            .cr2
+                            .modify(|_, w| w.ccpc().set_bit().ccus().clear_bit())
+                    }
+                }
+
+                /// Preload the channel enables and output modes, and transfer them together on a
+                /// commutation event triggered by the trigger output of `MASTER`, e.g. a
+                /// `hall::HallSensor` timer.
+                ///
+                /// `MASTER` is selected with a turbofish, e.g.
+                /// `enable_commutation_trigger::<TIM3>()`.
+                #[allow(unused_unsafe)]
+                pub fn enable_commutation_trigger<MASTER>(&mut self)
+                where
+                    $TIMX: InternalTrigger<MASTER>,
+                {
+                    let itr = <$TIMX as InternalTrigger<MASTER>>::ITR;
+                    unsafe {
+                        let tim = &*<$TIMX>::ptr();
+                        tim.smcr.modify(|_, w| w.ts().bits(itr));
+                        tim.cr2.modify(|_, w| w.ccpc().set_bit().ccus().set_bit());
+                    }
+                }
+
+                /// Stop preloading the channel enables and output modes
+                pub fn disable_commutation(&mut self) {
+                    unsafe { (*<$TIMX>::ptr()).cr2.modify(|_, w| w.ccpc().clear_bit()) }
+                }
+
+                /// Generate a commutation event by software
+                pub fn commutate(&mut self) {
+                    unsafe { (*<$TIMX>::ptr()).egr.write(|w| w.comg().set_bit()) }
+                }
+
+                /// Starts listening for commutation interrupts, where the next step is set
+                pub fn listen_commutation(&mut self) {
+                    unsafe { (*<$TIMX>::ptr()).dier.modify(|_, w| w.comie().set_bit()) }
+                }
+
+                /// Stops listening for commutation interrupts
+                pub fn unlisten_commutation(&mut self) {
+                    unsafe { (*<$TIMX>::ptr()).dier.modify(|_, w| w.comie().clear_bit()) }
+                }
+
+                /// Clears the commutation interrupt flag
+                pub fn clear_commutation_interrupt(&mut self) {
+                    // NOTE(unsafe) writing 1 to the other flags has no effect
+                    unsafe {
+                        (*<$TIMX>::ptr())
+                            .sr
+                            .write(|w| w.bits(<$TIMX as Flags>::FLAGS & !COMIF))
+                    }
+                }
+            }
+
+            complementary_pin!($TIMX, C1, PinC1N, 2, 3, 9);
+            complementary_pin!($TIMX, C2, PinC2N, 6, 7, 11);
+            complementary_pin!($TIMX, C3, PinC3N, 10, 11, 13);
+        )+
+    };
+}
+
+macro_rules! one_pulse_pin {
+    ($TIMX:ty, $C:ty, $ccr:ident, $bits:ty) => {
+        impl PwmChannels<$TIMX, $C> {
+            /// Set the pulse of the channel in one-pulse mode, starting `delay` ticks after the
+            /// trigger and lasting `width` ticks
+            ///
+            /// The end of the pulse is shared by all the channels of the timer, it is set by the
+            /// last call.
+            ///
+            /// Returns `Error::Pulse` if `width` is 0 or the end of the pulse does not fit in the
+            /// counter.
+            pub fn set_pulse(&mut self, delay: $bits, width: $bits) -> Result<(), Error> {
+                // The counter stops after ARR, the output is active from CCR to ARR included
+                let arr = match width.checked_sub(1) {
+                    Some(width) => delay.checked_add(width).ok_or(Error::Pulse)?,
+                    None => return Err(Error::Pulse),
+                };
+                unsafe {
+                    let tim = &*<$TIMX>::ptr();
+                    tim.$ccr.write(|w| w.ccr().bits(delay.into()));
+                    tim.arr.write(|w| w.bits(u32::from(arr)));
+                }
+                Ok(())
+            }
+
+            //NOTE(unsafe) atomic write with no side effects
+            /// Start the pulse of all the channels of the timer
+            #[inline]
+            pub fn start_pulse(&mut self) {
+                unsafe { bb::set(&(*<$TIMX>::ptr()).cr1, 0) }
+            }
+
+            //NOTE(unsafe) atomic read with no side effects
+            /// Returns `true` from the trigger to the end of the pulse
+            #[inline]
+            pub fn is_pulse_running(&self) -> bool {
+                unsafe { (*<$TIMX>::ptr()).cr1.read().cen().bit_is_set() }
+            }
+        }
+    };
+}
+
+macro_rules! pwm_one_pulse {
+    ($TIMX:ident, $bits:ty) => {
+        impl Timer<$TIMX> {
+            /// Configures the timer in one-pulse mode, counting at `tick_rate`.
+            ///
+            /// Each channel output is inactive until its delay after the trigger and active until
+            /// the end of the pulse, set with `set_pulse`. The timer stops at the end of the
+            /// pulse and waits for the next trigger.
+            ///
+            /// Returns `Error::Frequency` if the tick rate cannot be derived from the timer clock.
+            #[allow(unused_unsafe)]
+            pub fn one_pulse<P, PINS, T>(
+                self,
+                _pins: PINS,
+                tick_rate: T,
+                trigger: Trigger,
+            ) -> Result<PINS::Channels, Error>
+            where
+                PINS: Pins<$TIMX, P>,
+                T: Into<Hertz>,
+            {
+                let psc =
+                    timer::prescaler(self.clk, tick_rate.into()).map_err(|_| Error::Frequency)?;
+
+                // No preload, so a new pulse applies to the next trigger
+                if PINS::C1 {
+                    self.tim
+                        .ccmr1_output()
+                        .modify(|_, w| w.oc1pe().clear_bit().oc1m().pwm_mode2());
+                }
+                if PINS::C2 {
+                    self.tim
+                        .ccmr1_output()
+                        .modify(|_, w| w.oc2pe().clear_bit().oc2m().pwm_mode2());
+                }
+                if PINS::C3 {
+                    self.tim
+                        .ccmr2_output()
+                        .modify(|_, w| w.oc3pe().clear_bit().oc3m().pwm_mode2());
+                }
+                if PINS::C4 {
+                    self.tim
+                        .ccmr2_output()
+                        .modify(|_, w| w.oc4pe().clear_bit().oc4m().pwm_mode2());
+                }
+
+                self.tim.psc.write(|w| w.psc().bits(psc));
+
+                // Trigger update event to load the registers
+                self.tim.cr1.modify(|_, w| w.urs().set_bit());
+                self.tim.egr.write(|w| w.ug().set_bit());
+                self.tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                match trigger {
+                    Trigger::Software => {
+                        self.tim.smcr.modify(|_, w| unsafe { w.sms().bits(0b000) })
+                    }
+                    Trigger::Ti1 => {
+                        self.tim
+                            .ccmr1_input()
+                            .modify(|_, w| unsafe { w.cc1s().bits(0b01) });
+                        self.tim
+                            .ccer
+                            .modify(|_, w| w.cc1p().clear_bit().cc1np().clear_bit());
+                        // ts: TI1FP1, sms: trigger mode
+                        self.tim
+                            .smcr
+                            .modify(|_, w| unsafe { w.ts().bits(0b101).sms().bits(0b110) });
+                    }
+                    Trigger::Ti2 => {
+                        self.tim
+                            .ccmr1_input()
+                            .modify(|_, w| unsafe { w.cc2s().bits(0b01) });
+                        self.tim
+                            .ccer
+                            .modify(|_, w| w.cc2p().clear_bit().cc2np().clear_bit());
+                        // ts: TI2FP2, sms: trigger mode
+                        self.tim
+                            .smcr
+                            .modify(|_, w| unsafe { w.ts().bits(0b110).sms().bits(0b110) });
+                    }
+                    Trigger::Etr => {
+                        // ts: ETRF, sms: trigger mode
+                        self.tim
+                            .smcr
+                            .modify(|_, w| unsafe { w.ts().bits(0b111).sms().bits(0b110) });
+                    }
+                }
+
+                if PINS::C1 && trigger != Trigger::Ti1 {
+                    self.tim.ccer.modify(|_, w| w.cc1e().set_bit());
+                }
+                if PINS::C2 && trigger != Trigger::Ti2 {
+                    self.tim.ccer.modify(|_, w| w.cc2e().set_bit());
+                }
+                if PINS::C3 {
+                    self.tim.ccer.modify(|_, w| w.cc3e().set_bit());
+                }
+                if PINS::C4 {
+                    self.tim.ccer.modify(|_, w| w.cc4e().set_bit());
+                }
+
+                let _tim = &self.tim;
+                brk!($TIMX, _tim);
+                self.tim.cr1.write(|w| {
+                    w.cms()
+                        .bits(0b00)
+                        .dir()
+                        .clear_bit()
+                        .opm()
+                        .set_bit()
+                        .cen()
+                        .clear_bit()
+                });
+                //NOTE(unsafe) `PINS::Channels` is a ZST
+                Ok(unsafe { MaybeUninit::uninit().assume_init() })
+            }
+        }
+
+        one_pulse_pin!($TIMX, C1, ccr1, $bits);
+        one_pulse_pin!($TIMX, C2, ccr2, $bits);
+        one_pulse_pin!($TIMX, C3, ccr3, $bits);
+        one_pulse_pin!($TIMX, C4, ccr4, $bits);
+    };
+}
+
+macro_rules! pwm_dma {
+    ($TIMX:ty, $bits:ty, $($C:ty: $dba:literal),+) => {
+        $(
+            impl PwmChannels<$TIMX, $C> {
+                /// Streams duties with DMA on each update event, into `count` consecutive channels
+                /// starting with this one.
+                ///
+                /// The buffer holds `count` duties per PWM period, for the channels in order. The
+                /// duties apply from the following period.
+                ///
+                /// # Panics
+                ///
+                /// Panics if `count` is 0 or goes past channel 4.
+                #[allow(unused_unsafe)]
+                pub fn dma_duties(&mut self, count: u8) -> DutyDma<$TIMX> {
+                    assert!(count >= 1 && $dba + count <= 17);
+                    let tim = unsafe { &*<$TIMX>::ptr() };
+                    // dba: offset of the first CCR register in words, dbl: transfers per update
+                    tim.dcr
+                        .write(|w| unsafe { w.dba().bits($dba).dbl().bits(count - 1) });
+                    tim.dier.modify(|_, w| w.ude().set_bit());
+                    DutyDma { _tim: PhantomData }
+                }
+            }
+        )+
+
+        impl DutyDma<$TIMX> {
+            /// Stops the DMA requests on update events
+            pub fn release(self) {
+                unsafe { (*<$TIMX>::ptr()).dier.modify(|_, w| w.ude().clear_bit()) }
+            }
+        }
+
+        unsafe impl PeriAddress for DutyDma<$TIMX> {
+            #[inline(always)]
+            fn address(&self) -> u32 {
+                unsafe { &(*<$TIMX>::ptr()).dmar as *const _ as u32 }
+            }
+
+            type MemSize = $bits;
+        }
+
+        /// DMA is available for the duties based on the mappings for the DMA burst register
+        unsafe impl<STREAM, const CHANNEL: u8> DMASet<STREAM, MemoryToPeripheral, CHANNEL>
+            for DutyDma<$TIMX>
+        where
+            DMAR<$TIMX>: DMASet<STREAM, MemoryToPeripheral, CHANNEL>,
+        {
+        }
+    };
+}
+
 macro_rules! pwm_all_channels {
-    ($($TIMX:ident: ($timX:ident),)+) => {
+    ($($TIMX:ident: ($timX:ident, $bits:ty),)+) => {
         $(
             impl Timer<$TIMX> {
-                pub fn pwm<P, PINS, T>(self, _pins: PINS, freq: T) -> PINS::Channels
+                /// Configures the timer for PWM on the channels of `pins`, edge-aligned.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the frequency can not be reached, see `try_pwm`.
+                pub fn pwm<P, PINS, T>(self, pins: PINS, freq: T) -> PINS::Channels
+                where
+                    PINS: Pins<$TIMX, P>,
+                    T: Into<Hertz>,
+                {
+                    self.try_pwm(pins, freq).unwrap()
+                }
+
+                /// Configures the timer for PWM on the channels of `pins`, edge-aligned.
+                ///
+                /// Returns `Error::Frequency` if the frequency can not be reached with the timer
+                /// clock.
+                pub fn try_pwm<P, PINS, T>(self, _pins: PINS, freq: T) -> Result<PINS::Channels, Error>
                 where
                     PINS: Pins<$TIMX, P>,
                     T: Into<Hertz>,
                 {
+                    let (psc, arr) = prescaler(self.clk, freq.into(), <$bits>::MAX as u32)?;
+
                     if PINS::C1 {
                         self.tim.ccmr1_output()
                             .modify(|_, w| w.oc1pe().set_bit().oc1m().pwm_mode1() );
@@ -220,11 +1097,8 @@ macro_rules! pwm_all_channels {
                     // might as well enable for the auto-reload too
                     self.tim.cr1.modify(|_, w| w.arpe().set_bit());
 
-                    let ticks = self.clk.0 / freq.into().0;
-                    let psc = u16((ticks - 1) / (1 << 16)).unwrap();
                     self.tim.psc.write(|w| w.psc().bits(psc) );
-                    let arr = u16(ticks / u32(psc + 1)).unwrap();
-                    self.tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
+                    self.tim.arr.write(|w| unsafe { w.bits(arr) });
 
                     // Trigger update event to load the registers
                     self.tim.cr1.modify(|_, w| w.urs().set_bit());
@@ -244,14 +1118,27 @@ macro_rules! pwm_all_channels {
                             .set_bit()
                     );
                     //NOTE(unsafe) `PINS::Channels` is a ZST
-                    unsafe { MaybeUninit::uninit().assume_init() }
+                    Ok(unsafe { MaybeUninit::uninit().assume_init() })
                 }
             }
 
-            pwm_pin!($TIMX, C1, ccr1, 0);
-            pwm_pin!($TIMX, C2, ccr2, 4);
-            pwm_pin!($TIMX, C3, ccr3, 8);
-            pwm_pin!($TIMX, C4, ccr4, 12);
+            pwm_pin!($TIMX, C1, ccr1, 0, $bits);
+            pwm_pin!($TIMX, C2, ccr2, 4, $bits);
+            pwm_pin!($TIMX, C3, ccr3, 8, $bits);
+            pwm_pin!($TIMX, C4, ccr4, 12, $bits);
+
+            output_compare!($TIMX, C1, ccmr1_output, oc1m, oc1pe, 1);
+            output_compare!($TIMX, C2, ccmr1_output, oc2m, oc2pe, 5);
+            output_compare!($TIMX, C3, ccmr2_output, oc3m, oc3pe, 9);
+            output_compare!($TIMX, C4, ccmr2_output, oc4m, oc4pe, 13);
+
+            pwm_frequency!($TIMX, $bits, cms);
+            pwm_update!($TIMX);
+            pwm_alignment!($TIMX);
+
+            pwm_one_pulse!($TIMX, $bits);
+
+            pwm_dma!($TIMX, $bits, C1: 13, C2: 14, C3: 15, C4: 16);
         )+
     };
 }
@@ -260,11 +1147,30 @@ macro_rules! pwm_2_channels {
     ($($TIMX:ty: ($timX:ident),)+) => {
         $(
             impl Timer<$TIMX> {
-                pub fn pwm<P, PINS, T>(self, _pins: PINS, freq: T) -> PINS::Channels
+                /// Configures the timer for PWM on the channels of `pins`, edge-aligned.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the frequency can not be reached, see `try_pwm`.
+                pub fn pwm<P, PINS, T>(self, pins: PINS, freq: T) -> PINS::Channels
                 where
                     PINS: Pins<$TIMX, P>,
                     T: Into<Hertz>,
                 {
+                    self.try_pwm(pins, freq).unwrap()
+                }
+
+                /// Configures the timer for PWM on the channels of `pins`, edge-aligned.
+                ///
+                /// Returns `Error::Frequency` if the frequency can not be reached with the timer
+                /// clock.
+                pub fn try_pwm<P, PINS, T>(self, _pins: PINS, freq: T) -> Result<PINS::Channels, Error>
+                where
+                    PINS: Pins<$TIMX, P>,
+                    T: Into<Hertz>,
+                {
+                    let (psc, arr) = prescaler(self.clk, freq.into(), 0xffff)?;
+
                     if PINS::C1 {
                         //NOTE(unsafe) 6 is a valid value to write to oc1m
                         unsafe {
@@ -283,11 +1189,8 @@ macro_rules! pwm_2_channels {
                     // might as well enable for the auto-reload too
                     self.tim.cr1.modify(|_, w| w.arpe().set_bit());
 
-                    let ticks = self.clk.0 / freq.into().0;
-                    let psc = u16((ticks - 1) / (1 << 16)).unwrap();
                     self.tim.psc.write(|w| w.psc().bits(psc) );
-                    let arr = u16(ticks / u32(psc + 1)).unwrap();
-                    self.tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
+                    self.tim.arr.write(|w| unsafe { w.bits(arr) });
 
                     // Trigger update event to load the registers
                     self.tim.cr1.modify(|_, w| w.urs().set_bit());
@@ -301,12 +1204,18 @@ macro_rules! pwm_2_channels {
                             .set_bit()
                     );
                     //NOTE(unsafe) `PINS::Channels` is a ZST
-                    unsafe { MaybeUninit::uninit().assume_init() }
+                    Ok(unsafe { MaybeUninit::uninit().assume_init() })
                 }
             }
 
-            pwm_pin!($TIMX, C1, ccr1, 0);
-            pwm_pin!($TIMX, C2, ccr2, 4);
+            pwm_pin!($TIMX, C1, ccr1, 0, u16);
+            pwm_pin!($TIMX, C2, ccr2, 4, u16);
+
+            output_compare!($TIMX, C1, ccmr1_output, oc1m, oc1pe, 1);
+            output_compare!($TIMX, C2, ccmr1_output, oc2m, oc2pe, 5);
+
+            pwm_frequency!($TIMX, u16);
+            pwm_update!($TIMX);
         )+
     };
 }
@@ -315,11 +1224,30 @@ macro_rules! pwm_1_channel {
     ($($TIMX:ty: ($timX:ident),)+) => {
         $(
             impl Timer<$TIMX> {
-                pub fn pwm<P, PINS, T>(self, _pins: PINS, freq: T) -> PINS::Channels
+                /// Configures the timer for PWM on the channels of `pins`, edge-aligned.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the frequency can not be reached, see `try_pwm`.
+                pub fn pwm<P, PINS, T>(self, pins: PINS, freq: T) -> PINS::Channels
+                where
+                    PINS: Pins<$TIMX, P>,
+                    T: Into<Hertz>,
+                {
+                    self.try_pwm(pins, freq).unwrap()
+                }
+
+                /// Configures the timer for PWM on the channels of `pins`, edge-aligned.
+                ///
+                /// Returns `Error::Frequency` if the frequency can not be reached with the timer
+                /// clock.
+                pub fn try_pwm<P, PINS, T>(self, _pins: PINS, freq: T) -> Result<PINS::Channels, Error>
                 where
                     PINS: Pins<$TIMX, P>,
                     T: Into<Hertz>,
                 {
+                    let (psc, arr) = prescaler(self.clk, freq.into(), 0xffff)?;
+
                     if PINS::C1 {
                         //NOTE(unsafe) 6 is a valid value to write to oc1m
                         unsafe {
@@ -333,11 +1261,8 @@ macro_rules! pwm_1_channel {
                     // might as well enable for the auto-reload too
                     self.tim.cr1.modify(|_, w| w.arpe().set_bit());
 
-                    let ticks = self.clk.0 / freq.into().0;
-                    let psc = u16((ticks - 1) / (1 << 16)).unwrap();
                     self.tim.psc.write(|w| w.psc().bits(psc) );
-                    let arr = u16(ticks / u32(psc + 1)).unwrap();
-                    self.tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
+                    self.tim.arr.write(|w| unsafe { w.bits(arr) });
 
                     // Trigger update event to load the registers
                     self.tim.cr1.modify(|_, w| w.urs().set_bit());
@@ -349,11 +1274,16 @@ macro_rules! pwm_1_channel {
                             .set_bit()
                     );
                     //NOTE(unsafe) `PINS::Channels` is a ZST
-                    unsafe { MaybeUninit::uninit().assume_init() }
+                    Ok(unsafe { MaybeUninit::uninit().assume_init() })
                 }
             }
 
-            pwm_pin!($TIMX, C1, ccr1, 0);
+            pwm_pin!($TIMX, C1, ccr1, 0, u16);
+
+            output_compare!($TIMX, C1, ccmr1_output, oc1m, oc1pe, 1);
+
+            pwm_frequency!($TIMX, u16);
+            pwm_update!($TIMX);
         )+
     };
 }
@@ -362,6 +1292,8 @@ macro_rules! pwm_1_channel {
 macro_rules! pwm_pin_tim5 {
     ($TIMX:ty, $C:ty, $ccr: ident, $bit:literal) => {
         impl PwmChannels<$TIMX, $C> {
+            pwm_events!($TIMX, $bit);
+
             //NOTE(unsafe) atomic write with no side effects
             #[inline]
             pub fn disable(&mut self) {
@@ -423,11 +1355,30 @@ macro_rules! pwm_tim5_f410 {
     ($($TIMX:ty: ($timX:ident),)+) => {
         $(
             impl Timer<$TIMX> {
-                pub fn pwm<P, PINS, T>(self, _pins: PINS, freq: T) -> PINS::Channels
+                /// Configures the timer for PWM on the channels of `pins`, edge-aligned.
+                ///
+                /// # Panics
+                ///
+                /// Panics if the frequency can not be reached, see `try_pwm`.
+                pub fn pwm<P, PINS, T>(self, pins: PINS, freq: T) -> PINS::Channels
+                where
+                    PINS: Pins<$TIMX, P>,
+                    T: Into<Hertz>,
+                {
+                    self.try_pwm(pins, freq).unwrap()
+                }
+
+                /// Configures the timer for PWM on the channels of `pins`, edge-aligned.
+                ///
+                /// Returns `Error::Frequency` if the frequency can not be reached with the timer
+                /// clock.
+                pub fn try_pwm<P, PINS, T>(self, _pins: PINS, freq: T) -> Result<PINS::Channels, Error>
                 where
                     PINS: Pins<$TIMX, P>,
                     T: Into<Hertz>,
                 {
+                    let (psc, arr) = prescaler(self.clk, freq.into(), 0xffff)?;
+
                     if PINS::C1 {
                         self.tim.ccmr1_output()
                             .modify(|_, w| w.oc1pe().set_bit().oc1m().pwm_mode1() );
@@ -450,11 +1401,8 @@ macro_rules! pwm_tim5_f410 {
                     // might as well enable for the auto-reload too
                     self.tim.cr1.modify(|_, w| w.arpe().set_bit());
 
-                    let ticks = self.clk.0 / freq.into().0;
-                    let psc = u16((ticks - 1) / (1 << 16)).unwrap();
                     self.tim.psc.write(|w| w.psc().bits(psc) );
-                    let arr = u16(ticks / u32(psc + 1)).unwrap();
-                    self.tim.arr.write(|w| unsafe { w.arr_l().bits(arr) });
+                    self.tim.arr.write(|w| unsafe { w.arr_l().bits(arr as u16) });
 
                     // Trigger update event to load the registers
                     self.tim.cr1.modify(|_, w| w.urs().set_bit());
@@ -472,7 +1420,7 @@ macro_rules! pwm_tim5_f410 {
                             .set_bit()
                     );
                     //NOTE(unsafe) `PINS::Channels` is a ZST
-                    unsafe { MaybeUninit::uninit().assume_init() }
+                    Ok(unsafe { MaybeUninit::uninit().assume_init() })
                 }
             }
 
@@ -480,11 +1428,24 @@ macro_rules! pwm_tim5_f410 {
             pwm_pin_tim5!($TIMX, C2, ccr2, 4);
             pwm_pin_tim5!($TIMX, C3, ccr3, 8);
             pwm_pin_tim5!($TIMX, C4, ccr4, 12);
+
+            output_compare!($TIMX, C1, ccmr1_output, oc1m, oc1pe, 1);
+            output_compare!($TIMX, C2, ccmr1_output, oc2m, oc2pe, 5);
+            output_compare!($TIMX, C3, ccmr2_output, oc3m, oc3pe, 9);
+            output_compare!($TIMX, C4, ccmr2_output, oc4m, oc4pe, 13);
+
+            pwm_frequency!($TIMX, u16, cms);
+            pwm_update!($TIMX);
+            pwm_alignment!($TIMX);
         )+
     };
 }
 
-pwm_all_channels!(TIM1: (tim1),);
+pwm_all_channels!(TIM1: (tim1, u16),);
+
+idle_state!(TIM1: C1: 8, C2: 10, C3: 12, C4: 14);
+
+advanced_timer!(TIM1,);
 
 pwm_2_channels!(TIM9: (tim9),);
 
@@ -508,7 +1469,12 @@ pwm_1_channel!(TIM11: (tim11),);
     feature = "stm32f469",
     feature = "stm32f479"
 ))]
-pwm_all_channels!(TIM2: (tim2), TIM3: (tim3), TIM4: (tim4), TIM5: (tim5),);
+pwm_all_channels!(
+    TIM2: (tim2, u32),
+    TIM3: (tim3, u16),
+    TIM4: (tim4, u16),
+    TIM5: (tim5, u32),
+);
 
 #[cfg(any(
     feature = "stm32f401",
@@ -546,7 +1512,43 @@ pwm_1_channel!(TIM10: (tim10),);
     feature = "stm32f469",
     feature = "stm32f479"
 ))]
-pwm_all_channels!(TIM8: (tim8),);
+pwm_all_channels!(TIM8: (tim8, u16),);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+idle_state!(TIM8: C1: 8, C2: 10, C3: 12, C4: 14);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+advanced_timer!(TIM8,);
 
 #[cfg(any(
     feature = "stm32f405",
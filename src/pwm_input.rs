@@ -1,8 +1,19 @@
+#[cfg(not(feature = "stm32f410"))]
+use crate::dma::{
+    traits::{DMASet, PeriAddress, CCR1},
+    PeripheralToMemory,
+};
+#[cfg(not(feature = "stm32f410"))]
+use crate::timer::Flags;
 use crate::{
-    time::Hertz,
+    time::{Hertz, Period},
     timer::{PinC1, Timer},
 };
-use cast::u16;
+use core::marker::PhantomData;
+
+/// Update interrupt flag
+#[cfg(not(feature = "stm32f410"))]
+const UIF: u32 = 1 << 0;
 
 pub trait Pins<TIM> {}
 
@@ -34,10 +45,25 @@ impl<TIM, PC1> Pins<TIM> for PC1 where PC1: PinC1<TIM> {}
 ///             let duty = monitor.get_duty_cycle();
 /// }
 /// ```
+///
+/// The counter overflows when no period is captured during its whole range, which is reported as
+/// a signal loss by [`is_signal_lost`](PwmInput::is_signal_lost). The range is shortened by
+/// [`set_timeout`](PwmInput::set_timeout) and adapted to the signal by
+/// [`auto_range`](PwmInput::auto_range).
 pub struct PwmInput<TIM, PINS: Pins<TIM>> {
     tim: TIM,
     clk: Hertz,
     pins: PINS,
+    psc: u16,
+    timeout: Option<Period>,
+}
+
+/// DMA target reading the captured periods of a PWM input
+///
+/// Created by `dma_periods`, it is used with the stream of the `TIMx_CH1` DMA request. A circular
+/// transfer keeps the last periods in the buffer, e.g. to analyse the jitter.
+pub struct PeriodDma<TIM> {
+    _tim: PhantomData<TIM>,
 }
 
 #[cfg(not(feature = "stm32f410"))]
@@ -69,12 +95,14 @@ macro_rules! hal {
                 Sets the TIMer's prescaler such that the TIMer that it ticks at about the best-guess
                  frequency.
                 */
-                let ticks = self.clk.0 / best_guess.into().0;
-                let psc = u16((ticks - 1) / (1 << 16)).unwrap();
-                self.tim.psc.write(|w| w.psc().bits(psc));
+                let ticks = self.clk.0.checked_div(best_guess.into().0).unwrap_or(u32::MAX);
+                let psc = (ticks.max(1) - 1) / (1 << 16);
+                self.tim.psc.write(|w| w.psc().bits(psc.min(0xffff) as u16));
+                self.tim.arr.write(|w| unsafe { w.bits($bits::MAX as u32) });
 
-                // Seemingly this needs to be written to
-                // self.tim.arr.write(|w| w.arr().bits(u16::MAX));
+                // Only counter overflows, when no period was captured, generate update events
+                self.tim.cr1.modify(|_, w| w.urs().set_bit());
+                self.tim.egr.write(|w| w.ug().set_bit());
 
                 /*
                 For example, one can measure the period (in TIMx_CCR1 register) and the duty cycle (in
@@ -141,7 +169,13 @@ macro_rules! hal {
 
                 let Self { tim, clk } = self;
 
-                PwmInput { tim, clk, pins }
+                PwmInput {
+                    tim,
+                    clk,
+                    pins,
+                    psc: psc.min(0xffff) as u16,
+                    timeout: None,
+                }
             }
         }
 
@@ -153,7 +187,7 @@ macro_rules! hal {
                 // disable timer
                 self.tim.cr1.modify(|_, w| w.cen().disabled());
                 // decompose elements
-                let Self { tim, clk, pins } = self;
+                let Self { tim, clk, pins, .. } = self;
                 // and return them to the caller
                 (Timer { tim, clk }, pins)
             }
@@ -179,11 +213,141 @@ macro_rules! hal {
             pub fn is_valid_capture(&self) -> bool {
                 self.get_duty_cycle_clocks() != self.get_period_clocks()
             }
+            /// Counter frequency, the unit of the clock cycles
+            pub fn tick_rate(&self) -> Hertz {
+                Hertz(self.clk.0 / (u32::from(self.psc) + 1))
+            }
+            /// Frequency of the PWM signal, 0 Hz when the signal is lost
+            pub fn get_frequency(&self) -> Hertz {
+                let period = u64::from(self.get_period_clocks()) * (u64::from(self.psc) + 1);
+                if period == 0 || self.is_signal_lost() {
+                    return Hertz(0);
+                }
+                Hertz((u64::from(self.clk.0) / period) as u32)
+            }
+            /// Returns whether the counter overflowed without a period capture, because the signal
+            /// stopped or its period is longer than the counter range or the timeout.
+            ///
+            /// The flag is kept until [`clear_signal_lost`](Self::clear_signal_lost).
+            pub fn is_signal_lost(&self) -> bool {
+                self.tim.sr.read().uif().bit_is_set()
+            }
+            /// Clears the signal loss flag
+            pub fn clear_signal_lost(&mut self) {
+                // NOTE(unsafe) writing 1 to the other flags has no effect
+                self.tim
+                    .sr
+                    .write(|w| unsafe { w.bits(<$TIM as Flags>::FLAGS & !UIF) });
+            }
+            /// Starts listening for signal loss interrupts
+            pub fn listen_signal_loss(&mut self) {
+                self.tim.dier.modify(|_, w| w.uie().set_bit());
+            }
+            /// Stops listening for signal loss interrupts
+            pub fn unlisten_signal_loss(&mut self) {
+                self.tim.dier.modify(|_, w| w.uie().clear_bit());
+            }
+            /// Reports a signal loss when no period is captured during `timeout`, limited by the
+            /// counter range
+            pub fn set_timeout<T: Into<Period>>(&mut self, timeout: T) {
+                self.timeout = Some(timeout.into());
+                self.set_prescaler(self.psc);
+            }
+            /// Reports a signal loss only on a counter overflow
+            pub fn clear_timeout(&mut self) {
+                self.timeout = None;
+                self.set_prescaler(self.psc);
+            }
+            /// Adapts the prescaler to the last captured period, for the best resolution without
+            /// overflowing the counter. Without timeout, a signal loss doubles the prescaler to
+            /// catch longer periods.
+            ///
+            /// Returns `true` when the prescaler changed. The counter then restarts: the next
+            /// capture is incomplete and has to be discarded, and the signal loss flag is cleared.
+            pub fn auto_range(&mut self) -> bool {
+                let range = u64::from(self.tim.arr.read().bits()) + 1;
+                let psc = if self.is_signal_lost() {
+                    if self.timeout.is_some() {
+                        return false;
+                    }
+                    u32::from(self.psc) * 2 + 1
+                } else {
+                    let period = u64::from(self.get_period_clocks());
+                    // Keep the period between a quarter and 7/8 of the range
+                    if period == 0 || (period >= range / 4 && period <= range / 8 * 7) {
+                        return false;
+                    }
+                    // New prescaler for a period of half the range
+                    let clocks = period * (u64::from(self.psc) + 1);
+                    ((clocks * 2 + range - 1) / range).max(1).min(1 << 16) as u32 - 1
+                };
+                let psc = psc.min(0xffff) as u16;
+                if psc == self.psc {
+                    return false;
+                }
+                self.set_prescaler(psc);
+                true
+            }
+            #[allow(unused_unsafe)]
+            fn set_prescaler(&mut self, psc: u16) {
+                self.psc = psc;
+                let tick_rate = self.tick_rate();
+                let arr = self
+                    .timeout
+                    .and_then(|timeout| timeout.cycles(tick_rate))
+                    .map_or($bits::MAX as u64, |ticks| ticks.max(1).min($bits::MAX as u64));
+                self.tim.psc.write(|w| w.psc().bits(psc));
+                self.tim.arr.write(|w| unsafe { w.bits(arr as u32) });
+                // Restart the counter with the new values, without update event flag
+                self.tim.egr.write(|w| w.ug().set_bit());
+                self.clear_signal_lost();
+            }
+        }
+        )+
+}}
+
+#[cfg(not(feature = "stm32f410"))]
+macro_rules! dma {
+    ($($TIM:ident: ($bits:ident),)+) => {
+        $(
+        impl<PINS> PwmInput<$TIM, PINS>
+        where
+            PINS: Pins<$TIM>,
+        {
+            /// Requests a DMA transfer of the period on each capture
+            pub fn dma_periods(&mut self) -> PeriodDma<$TIM> {
+                self.tim.dier.modify(|_, w| w.cc1de().set_bit());
+                PeriodDma { _tim: PhantomData }
+            }
+        }
+
+        impl PeriodDma<$TIM> {
+            /// Stops the DMA requests on captures
+            pub fn release(self) {
+                unsafe { (*<$TIM>::ptr()).dier.modify(|_, w| w.cc1de().clear_bit()) }
+            }
+        }
+
+        unsafe impl PeriAddress for PeriodDma<$TIM> {
+            #[inline(always)]
+            fn address(&self) -> u32 {
+                unsafe { &(*<$TIM>::ptr()).ccr1 as *const _ as u32 }
+            }
+
+            type MemSize = $bits;
+        }
+
+        /// DMA is available for the periods based on the mappings for the channel 1 captures
+        unsafe impl<STREAM, const CHANNEL: u8> DMASet<STREAM, PeripheralToMemory, CHANNEL>
+            for PeriodDma<$TIM>
+        where
+            CCR1<$TIM>: DMASet<STREAM, PeripheralToMemory, CHANNEL>,
+        {
         }
         )+
 }}
 
-#[cfg(any(feature = "stm32f411",))]
+#[cfg(feature = "stm32f411")]
 /* red group */
 hal! {
     TIM4: (u16),
@@ -247,3 +411,54 @@ hal! {
     TIM5: (u32),
     TIM9: (u16),
 }
+
+/* timers with a DMA request on the channel 1 captures */
+#[cfg(not(feature = "stm32f410"))]
+dma! {
+    TIM1: (u16),
+    TIM5: (u32),
+}
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479",
+))]
+dma! {
+    TIM2: (u32),
+    TIM3: (u16),
+    TIM4: (u16),
+}
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479",
+))]
+dma! {
+    TIM8: (u16),
+}
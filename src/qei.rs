@@ -1,13 +1,26 @@
 //! # Quadrature Encoder Interface
+//!
+//! The timer counts the edges of the two encoder inputs, up or down depending on their phase.
+//! An optional index pulse (Z) on channel 3 is captured to reset or latch the count once per
+//! revolution, and counter overflows can be counted in the update interrupt to get an `i64`
+//! position not limited to the counter range.
 use crate::{
     hal::{self, Direction},
     pac::RCC,
     rcc::{Enable, Reset},
+    timer::Flags,
 };
 
-pub trait Pins<TIM> {}
+/// Update interrupt flag
+const UIF: u32 = 1 << 0;
+
+pub trait Pins<TIM> {
+    /// Whether the pins include the index input on channel 3
+    const INDEX: bool = false;
+}
 use crate::timer::PinC1;
 use crate::timer::PinC2;
+use crate::timer::PinC3;
 
 impl<TIM, PC1, PC2> Pins<TIM> for (PC1, PC2)
 where
@@ -16,29 +29,254 @@ where
 {
 }
 
+impl<TIM, PC1, PC2, PC3> Pins<TIM> for (PC1, PC2, PC3)
+where
+    PC1: PinC1<TIM>,
+    PC2: PinC2<TIM>,
+    PC3: PinC3<TIM>,
+{
+    const INDEX: bool = true;
+}
+
+/// Encoder counting mode
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Mode {
+    /// Counts on the edges of input 1 only (x2 resolution)
+    X2Ti1 = 1,
+    /// Counts on the edges of input 2 only (x2 resolution)
+    X2Ti2 = 2,
+    /// Counts on the edges of both inputs (x4 resolution)
+    X4 = 3,
+}
+
+/// Encoder input polarity
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Polarity {
+    /// Input used as is
+    Normal,
+    /// Input inverted, which reverses the counting direction when applied to one input
+    Inverted,
+}
+
+/// Action taken on a rising edge of the index pulse
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum IndexMode {
+    /// The position is reset to zero at the index
+    Reset,
+    /// The position at the index is stored, see [`Qei::index_position`]
+    Latch,
+}
+
+/// QEI configuration error
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Error {
+    /// The modulus is 0 or larger than the counter range
+    Modulus,
+}
+
+/// Interrupt events
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Event {
+    /// Counter overflow or underflow, needed to track the extended position
+    Overflow,
+    /// Index pulse, when the pins include the index input
+    Index,
+}
+
+/// Quadrature encoder configuration
+#[derive(Debug, Copy, Clone)]
+pub struct QeiConfig {
+    /// Counting mode
+    pub mode: Mode,
+    /// Polarity of input 1
+    pub polarity1: Polarity,
+    /// Polarity of input 2
+    pub polarity2: Polarity,
+    /// Digital filter of input 1, the `IC1F` value from 0 (no filter) to 15
+    pub filter1: u8,
+    /// Digital filter of input 2, the `IC2F` value from 0 (no filter) to 15
+    pub filter2: u8,
+    /// Counter modulus, the count wraps to 0 after `modulus - 1`. The whole counter range when
+    /// `None`.
+    pub modulus: Option<u32>,
+    /// Action on the index pulse, ignored when the pins have no index input
+    pub index: IndexMode,
+}
+
+impl QeiConfig {
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn polarity(mut self, polarity1: Polarity, polarity2: Polarity) -> Self {
+        self.polarity1 = polarity1;
+        self.polarity2 = polarity2;
+        self
+    }
+
+    pub fn filter(mut self, filter1: u8, filter2: u8) -> Self {
+        self.filter1 = filter1;
+        self.filter2 = filter2;
+        self
+    }
+
+    /// Sets the counter modulus, the counts per revolution of a rotary encoder
+    ///
+    /// Must be at least 1 and at most the counter range, 65536 on 16-bit timers, `with_config`
+    /// returns `Error::Modulus` otherwise.
+    pub fn modulus(mut self, modulus: u32) -> Self {
+        self.modulus = Some(modulus);
+        self
+    }
+
+    pub fn index(mut self, index: IndexMode) -> Self {
+        self.index = index;
+        self
+    }
+}
+
+impl Default for QeiConfig {
+    fn default() -> QeiConfig {
+        QeiConfig {
+            mode: Mode::X4,
+            polarity1: Polarity::Normal,
+            polarity2: Polarity::Normal,
+            filter1: 0,
+            filter2: 0,
+            modulus: None,
+            index: IndexMode::Latch,
+        }
+    }
+}
+
 /// Hardware quadrature encoder interface peripheral
 pub struct Qei<TIM, PINS> {
     tim: TIM,
     pins: PINS,
+    index: IndexMode,
+    modulus: i64,
+    overflows: i64,
+    offset: i64,
+    latched: Option<i64>,
 }
 
 impl<TIM: Instance, PINS> Qei<TIM, PINS> {
     /// Configures a TIM peripheral as a quadrature encoder interface input
     pub fn new(tim: TIM, pins: PINS) -> Self
+    where
+        PINS: Pins<TIM>,
+    {
+        Self::init(tim, pins, QeiConfig::default())
+    }
+
+    /// Configures a TIM peripheral as a quadrature encoder interface input with `config`
+    ///
+    /// Returns `Error::Modulus` if the modulus is 0 or larger than the counter range.
+    pub fn with_config(tim: TIM, pins: PINS, config: QeiConfig) -> Result<Self, Error>
+    where
+        PINS: Pins<TIM>,
+    {
+        match config.modulus {
+            Some(modulus) if modulus == 0 || u64::from(modulus) > TIM::RANGE => Err(Error::Modulus),
+            _ => Ok(Self::init(tim, pins, config)),
+        }
+    }
+
+    fn init(tim: TIM, pins: PINS, config: QeiConfig) -> Self
     where
         PINS: Pins<TIM>,
     {
         TIM::setup_clocks();
 
-        tim.setup_qei();
+        tim.setup_qei(&config, PINS::INDEX);
+
+        let modulus = tim.read_modulus() as i64;
+        Qei {
+            tim,
+            pins,
+            index: config.index,
+            modulus,
+            overflows: 0,
+            offset: 0,
+            latched: None,
+        }
+    }
+
+    /// Starts listening for an `event`
+    pub fn listen(&mut self, event: Event) {
+        self.tim.set_interrupt(event, true);
+    }
+
+    /// Stops listening for an `event`
+    pub fn unlisten(&mut self, event: Event) {
+        self.tim.set_interrupt(event, false);
+    }
+
+    /// Handles the overflow and index events, call this from the timer interrupt
+    ///
+    /// Overflows must be handled within half a counter period for the position to stay correct.
+    pub fn on_interrupt(&mut self) {
+        if self.tim.overflow_pending() {
+            self.tim.clear_overflow();
+            self.overflows += self.wrap(i64::from(self.tim.read_count_raw()));
+        }
+        if let Some(captured) = self.tim.read_index() {
+            let count = i64::from(self.tim.read_count_raw());
+            // The capture may be on the other side of a wrap than the current count
+            let mut delta = i64::from(captured) - count;
+            if delta > self.modulus / 2 {
+                delta -= self.modulus;
+            } else if delta < -self.modulus / 2 {
+                delta += self.modulus;
+            }
+            let index = self.overflows + count + delta;
+            match self.index {
+                IndexMode::Reset => self.offset = index,
+                IndexMode::Latch => self.latched = Some(index - self.offset),
+            }
+        }
+    }
+
+    /// Returns the position extended over counter overflows
+    pub fn position(&self) -> i64 {
+        loop {
+            // An overflow not yet handled by `on_interrupt` shows up as a pending update flag,
+            // retry if it appeared while reading the counter
+            let before = self.tim.overflow_pending();
+            let count = i64::from(self.tim.read_count_raw());
+            let after = self.tim.overflow_pending();
+            if before == after {
+                let pending = if after { self.wrap(count) } else { 0 };
+                return self.overflows + pending + count - self.offset;
+            }
+        }
+    }
 
-        Qei { tim, pins }
+    /// Returns the position at the last index pulse, with [`IndexMode::Latch`]
+    pub fn index_position(&self) -> Option<i64> {
+        self.latched
+    }
+
+    /// Sets the current position, clearing the latched index position
+    pub fn set_position(&mut self, position: i64) {
+        self.offset = self.position() + self.offset - position;
+        self.latched = None;
     }
 
     /// Releases the TIM peripheral and QEI pins
     pub fn release(self) -> (TIM, PINS) {
         (self.tim, self.pins)
     }
+
+    // Right after an overflow the count is near zero, right after an underflow near the top
+    fn wrap(&self, count: i64) -> i64 {
+        if count < self.modulus / 2 {
+            self.modulus
+        } else {
+            -self.modulus
+        }
+    }
 }
 
 impl<TIM: Instance, PINS> hal::Qei for Qei<TIM, PINS> {
@@ -59,11 +297,19 @@ impl<TIM: Instance, PINS> hal::Qei for Qei<TIM, PINS> {
 
 pub trait Instance: crate::Sealed {
     type Count;
+    /// Number of counter values
+    const RANGE: u64;
 
     fn setup_clocks();
-    fn setup_qei(&self);
+    fn setup_qei(&self, config: &QeiConfig, index: bool);
     fn read_count(&self) -> Self::Count;
+    fn read_count_raw(&self) -> u32;
     fn read_direction(&self) -> bool;
+    fn read_modulus(&self) -> u64;
+    fn set_interrupt(&self, event: Event, enable: bool);
+    fn overflow_pending(&self) -> bool;
+    fn clear_overflow(&self);
+    fn read_index(&self) -> Option<u32>;
 }
 
 macro_rules! hal {
@@ -71,6 +317,7 @@ macro_rules! hal {
         $(
             impl Instance for $TIM {
                 type Count = $bits;
+                const RANGE: u64 = <$bits>::MAX as u64 + 1;
 
                 fn setup_clocks() {
                     unsafe {
@@ -82,26 +329,47 @@ macro_rules! hal {
                     }
                 }
 
-                fn setup_qei(&self) {
-                    // Configure TxC1 and TxC2 as captures
-                    self.ccmr1_output()
-                        .write(|w| unsafe { w.cc1s().bits(0b01).cc2s().bits(0b01) });
-                    // enable and configure to capture on rising edge
+                fn setup_qei(&self, config: &QeiConfig, index: bool) {
+                    // Configure TxC1 and TxC2 as captures, with their filters
+                    // some chip variants declare `.bits()` as unsafe, some don't
+                    #[allow(unused_unsafe)]
+                    self.ccmr1_input().write(|w| unsafe {
+                        w.cc1s()
+                            .bits(0b01)
+                            .ic1f()
+                            .bits(config.filter1 & 0xf)
+                            .cc2s()
+                            .bits(0b01)
+                            .ic2f()
+                            .bits(config.filter2 & 0xf)
+                    });
+                    if index {
+                        // Capture the count on the rising edge of the index input
+                        #[allow(unused_unsafe)]
+                        self.ccmr2_input()
+                            .write(|w| unsafe { w.cc3s().bits(0b01) });
+                    }
+                    // enable and configure the input polarities
                     self.ccer.write(|w| {
                         w.cc1e()
                             .set_bit()
                             .cc1p()
-                            .clear_bit()
+                            .bit(config.polarity1 == Polarity::Inverted)
                             .cc2e()
                             .set_bit()
                             .cc2p()
-                            .clear_bit()
+                            .bit(config.polarity2 == Polarity::Inverted)
+                            .cc3e()
+                            .bit(index)
                     });
                     // configure as quadrature encoder
-                    // some chip variants declare `.bits()` as unsafe, some don't
                     #[allow(unused_unsafe)]
-                    self.smcr.write(|w| unsafe { w.sms().bits(3) });
-                    self.arr.write(|w| unsafe { w.bits(core::u32::MAX) });
+                    self.smcr.write(|w| unsafe { w.sms().bits(config.mode as u8) });
+                    let arr = match config.modulus {
+                        Some(modulus) => modulus - 1,
+                        None => u32::MAX,
+                    };
+                    self.arr.write(|w| unsafe { w.bits(arr) });
                     self.cr1.write(|w| w.cen().set_bit());
                 }
 
@@ -109,9 +377,42 @@ macro_rules! hal {
                     self.cnt.read().bits() as Self::Count
                 }
 
+                fn read_count_raw(&self) -> u32 {
+                    self.cnt.read().bits() & (<$bits>::MAX as u32)
+                }
+
                 fn read_direction(&self) -> bool {
                     self.cr1.read().dir().bit_is_clear()
                 }
+
+                fn read_modulus(&self) -> u64 {
+                    u64::from(self.arr.read().bits() & (<$bits>::MAX as u32)) + 1
+                }
+
+                fn set_interrupt(&self, event: Event, enable: bool) {
+                    match event {
+                        Event::Overflow => self.dier.modify(|_, w| w.uie().bit(enable)),
+                        Event::Index => self.dier.modify(|_, w| w.cc3ie().bit(enable)),
+                    }
+                }
+
+                fn overflow_pending(&self) -> bool {
+                    self.sr.read().uif().bit_is_set()
+                }
+
+                fn clear_overflow(&self) {
+                    // NOTE(unsafe) writing 1 to the other flags has no effect
+                    self.sr.write(|w| unsafe { w.bits(<$TIM as Flags>::FLAGS & !UIF) });
+                }
+
+                fn read_index(&self) -> Option<u32> {
+                    if self.sr.read().cc3if().bit_is_set() {
+                        // reading the capture clears the flag
+                        Some(self.ccr3.read().bits())
+                    } else {
+                        None
+                    }
+                }
             }
 
             impl<PINS> Qei<$TIM, PINS> {
@@ -109,6 +109,16 @@ bus! {
     RNG => (AHB2, 6),
 }
 
+#[cfg(feature = "stm32f410")]
+bus! {
+    LPTIM1 => (APB1, 9),
+}
+
+#[cfg(any(feature = "stm32f413", feature = "stm32f423"))]
+bus! {
+    LPTIM => (APB1, 9),
+}
+
 #[cfg(feature = "otg-fs")]
 bus! {
     OTG_FS_GLOBAL => (AHB2, 7),
@@ -1,6 +1,8 @@
+use core::marker::PhantomData;
 use core::ops::Deref;
 use core::ptr;
 
+use embedded_hal::digital::v2::OutputPin;
 use embedded_hal::spi;
 pub use embedded_hal::spi::{Mode, Phase, Polarity};
 
@@ -38,7 +40,10 @@ use crate::pac::SPI5;
 #[cfg(feature = "spi6")]
 use crate::pac::SPI6;
 
+use crate::dma::traits::{Channel, DMASet, PeriAddress, Stream};
+use crate::dma::{config::DmaConfig, ChannelX, MemoryToPeripheral, PeripheralToMemory, Transfer};
 use crate::gpio::{Alternate, NoPin};
+use embedded_dma::{StaticReadBuffer, StaticWriteBuffer};
 
 use crate::rcc::Clocks;
 use crate::time::Hertz;
@@ -59,6 +64,7 @@ pub trait Pins<SPI> {}
 pub trait PinSck<SPI> {}
 pub trait PinMiso<SPI> {}
 pub trait PinMosi<SPI> {}
+pub trait PinNss<SPI> {}
 
 impl<SPI, SCK, MISO, MOSI> Pins<SPI> for (SCK, MISO, MOSI)
 where
@@ -68,6 +74,15 @@ where
 {
 }
 
+impl<SPI, SCK, MISO, MOSI, NSS> Pins<SPI> for (SCK, MISO, MOSI, NSS)
+where
+    SCK: PinSck<SPI>,
+    MISO: PinMiso<SPI>,
+    MOSI: PinMosi<SPI>,
+    NSS: PinNss<SPI>,
+{
+}
+
 /// A filler type for when the SCK pin is unnecessary
 pub type NoSck = NoPin;
 /// A filler type for when the Miso pin is unnecessary
@@ -80,7 +95,7 @@ impl<SPI> PinMiso<SPI> for NoPin where SPI: Instance {}
 impl<SPI> PinMosi<SPI> for NoPin where SPI: Instance {}
 
 macro_rules! pins {
-    ($($SPIX:ty: SCK: [$($SCK:ty),*] MISO: [$($MISO:ty),*] MOSI: [$($MOSI:ty),*])+) => {
+    ($($SPIX:ty: SCK: [$($SCK:ty),*] MISO: [$($MISO:ty),*] MOSI: [$($MOSI:ty),*] NSS: [$($NSS:ty),*])+) => {
         $(
             $(
                 impl PinSck<$SPIX> for $SCK {}
@@ -91,6 +106,9 @@ macro_rules! pins {
             $(
                 impl PinMosi<$SPIX> for $MOSI {}
             )*
+            $(
+                impl PinNss<$SPIX> for $NSS {}
+            )*
         )+
     }
 }
@@ -109,6 +127,10 @@ pins! {
             gpioa::PA7<Alternate<5>>,
             gpiob::PB5<Alternate<5>>
         ]
+        NSS: [
+            gpioa::PA4<Alternate<5>>,
+            gpioa::PA15<Alternate<5>>
+        ]
 
     SPI2:
         SCK: [
@@ -123,6 +145,10 @@ pins! {
             gpiob::PB15<Alternate<5>>,
             gpioc::PC3<Alternate<5>>
         ]
+        NSS: [
+            gpiob::PB9<Alternate<5>>,
+            gpiob::PB12<Alternate<5>>
+        ]
 }
 
 #[cfg(feature = "spi3")]
@@ -140,6 +166,10 @@ pins! {
             gpiob::PB5<Alternate<6>>,
             gpioc::PC12<Alternate<6>>
         ]
+        NSS: [
+            gpioa::PA4<Alternate<6>>,
+            gpioa::PA15<Alternate<6>>
+        ]
 }
 
 #[cfg(any(
@@ -161,10 +191,12 @@ pins! {
         SCK: [gpiod::PD3<Alternate<5>>]
         MISO: []
         MOSI: []
+        NSS: []
     SPI3:
         SCK: []
         MISO: []
         MOSI: [gpiod::PD6<Alternate<5>>]
+        NSS: []
     SPI4:
         SCK: [
             gpioe::PE2<Alternate<5>>,
@@ -178,6 +210,10 @@ pins! {
             gpioe::PE6<Alternate<5>>,
             gpioe::PE14<Alternate<5>>
         ]
+        NSS: [
+            gpioe::PE4<Alternate<5>>,
+            gpioe::PE11<Alternate<5>>
+        ]
 }
 
 #[cfg(any(
@@ -197,6 +233,7 @@ pins! {
         SCK: [gpioi::PI1<Alternate<5>>]
         MISO: [gpioi::PI2<Alternate<5>>]
         MOSI: [gpioi::PI3<Alternate<5>>]
+        NSS: [gpioi::PI0<Alternate<5>>]
 }
 
 #[cfg(any(
@@ -212,6 +249,7 @@ pins! {
         SCK: [gpioc::PC7<Alternate<5>>]
         MISO: []
         MOSI: []
+        NSS: []
 }
 
 #[cfg(any(
@@ -233,6 +271,9 @@ pins! {
             gpioa::PA10<Alternate<6>>,
             gpiob::PB8<Alternate<6>>
         ]
+        NSS: [
+            gpiob::PB1<Alternate<6>>
+        ]
 }
 
 #[cfg(any(
@@ -246,10 +287,12 @@ pins! {
         SCK: [gpiob::PB12<Alternate<7>>]
         MISO: []
         MOSI: []
+        NSS: []
     SPI4:
         SCK: [gpiob::PB13<Alternate<6>>]
         MISO: [gpioa::PA11<Alternate<6>>]
         MOSI: [gpioa::PA1<Alternate<5>>]
+        NSS: [gpiob::PB12<Alternate<6>>]
     SPI5:
         SCK: [
             gpioe::PE2<Alternate<6>>,
@@ -263,6 +306,10 @@ pins! {
             gpioe::PE6<Alternate<6>>,
             gpioe::PE14<Alternate<6>>
         ]
+        NSS: [
+            gpioe::PE4<Alternate<6>>,
+            gpioe::PE11<Alternate<6>>
+        ]
 }
 
 #[cfg(any(feature = "stm32f413", feature = "stm32f423"))]
@@ -271,6 +318,7 @@ pins! {
         SCK: [gpioa::PA9<Alternate<5>>]
         MISO: [gpioa::PA12<Alternate<5>>]
         MOSI: [gpioa::PA10<Alternate<5>>]
+        NSS: [gpioa::PA11<Alternate<5>>]
 }
 
 #[cfg(any(
@@ -295,6 +343,10 @@ pins! {
             gpiof::PF9<Alternate<5>>,
             gpiof::PF11<Alternate<5>>
         ]
+        NSS: [
+            gpiof::PF6<Alternate<5>>,
+            gpioh::PH5<Alternate<5>>
+        ]
 
     SPI6:
         SCK: [
@@ -306,6 +358,9 @@ pins! {
         MOSI: [
             gpiog::PG14<Alternate<5>>
         ]
+        NSS: [
+            gpiog::PG8<Alternate<5>>
+        ]
 }
 
 #[cfg(any(feature = "stm32f446"))]
@@ -314,6 +369,7 @@ pins! {
         SCK: [gpioa::PA9<Alternate<5>>]
         MISO: []
         MOSI: [gpioc::PC1<Alternate<7>>]
+        NSS: []
 
     SPI3:
         SCK: []
@@ -323,6 +379,7 @@ pins! {
             gpiob::PB2<Alternate<7>>,
             gpiod::PD0<Alternate<6>>
         ]
+        NSS: []
 
     SPI4:
         SCK: [gpiog::PG11<Alternate<6>>]
@@ -331,6 +388,7 @@ pins! {
             gpiod::PD0<Alternate<5>>
         ]
         MOSI: [gpiog::PG13<Alternate<6>>]
+        NSS: []
 }
 
 #[cfg(any(feature = "stm32f469", feature = "stm32f479"))]
@@ -339,6 +397,7 @@ pins! {
         SCK: [gpioa::PA9<Alternate<5>>]
         MISO: []
         MOSI: [gpioc::PC1<Alternate<5>>]
+        NSS: []
 }
 
 /// Interrupt events
@@ -355,24 +414,70 @@ pub enum Event {
 pub struct TransferModeNormal;
 /// BIDI mode - use TX pin as RX then spi receive data
 pub struct TransferModeBidi;
+/// Receive-only mode - the master generates the clock only while receiving, MOSI is unused
+pub struct TransferModeRxOnly {
+    /// Core clock cycles per SPI clock cycle
+    sck_cycles: u32,
+}
+
+/// Order of the bits in a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitOrder {
+    /// Most significant bit first (default)
+    MsbFirst,
+    /// Least significant bit first
+    LsbFirst,
+}
+
+/// Frame format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// Motorola frame format (default)
+    Motorola,
+    /// TI synchronous serial frame format
+    Ti,
+}
+
+/// Size of the SPI frames, `u8` (the default) or `u16`
+pub trait FrameSize: Copy + crate::Sealed {
+    #[doc(hidden)]
+    const DFF: bool;
+}
+
+impl crate::Sealed for u8 {}
+impl FrameSize for u8 {
+    const DFF: bool = false;
+}
+
+impl crate::Sealed for u16 {}
+impl FrameSize for u16 {
+    const DFF: bool = true;
+}
 
 #[derive(Debug)]
-pub struct Spi<SPI, PINS, TRANSFER_MODE> {
+pub struct Spi<SPI, PINS, TRANSFER_MODE, WORD = u8> {
     spi: SPI,
     pins: PINS,
     transfer_mode: TRANSFER_MODE,
+    _word: PhantomData<WORD>,
 }
 
 // Implemented by all SPI instances
 pub trait Instance:
     crate::Sealed + Deref<Target = spi1::RegisterBlock> + rcc::Enable + rcc::Reset + rcc::GetBusFreq
 {
+    #[doc(hidden)]
+    fn ptr() -> *const spi1::RegisterBlock;
 }
 
 // Implemented by all SPI instances
 macro_rules! spi {
     ($SPI:ident: ($spi:ident)) => {
-        impl Instance for $SPI {}
+        impl Instance for $SPI {
+            fn ptr() -> *const spi1::RegisterBlock {
+                <$SPI>::ptr() as *const _
+            }
+        }
 
         impl<SCK, MISO, MOSI> Spi<$SPI, (SCK, MISO, MOSI), TransferModeNormal>
         where
@@ -428,6 +533,7 @@ where
             spi,
             pins,
             transfer_mode: TransferModeNormal,
+            _word: PhantomData,
         }
         .pre_init(mode, freq, SPI::get_frequency(&clocks))
         .init()
@@ -465,6 +571,7 @@ where
             spi,
             pins,
             transfer_mode: TransferModeBidi,
+            _word: PhantomData,
         }
         .pre_init(mode, freq, SPI::get_frequency(&clocks))
         .init()
@@ -477,7 +584,91 @@ where
     }
 }
 
-impl<SPI, PINS> Spi<SPI, PINS, TransferModeNormal>
+impl<SPI, SCK, MISO, MOSI> Spi<SPI, (SCK, MISO, MOSI), TransferModeRxOnly>
+where
+    SPI: Instance,
+    SCK: PinSck<SPI>,
+    MISO: PinMiso<SPI>,
+    MOSI: PinMosi<SPI>,
+{
+    /// Creates a receive-only SPI master, `MOSI` is not driven and can be `NoMosi`.
+    pub fn new_rx_only(
+        spi: SPI,
+        pins: (SCK, MISO, MOSI),
+        mode: Mode,
+        freq: Hertz,
+        clocks: Clocks,
+    ) -> Self {
+        unsafe {
+            // NOTE(unsafe) this reference will only be used for atomic writes with no side effects.
+            let rcc = &(*RCC::ptr());
+            SPI::enable(rcc);
+            SPI::reset(rcc);
+        }
+
+        let pclk = SPI::get_frequency(&clocks);
+        let spi = Spi {
+            spi,
+            pins,
+            transfer_mode: TransferModeRxOnly { sck_cycles: 0 },
+            _word: PhantomData,
+        }
+        .pre_init(mode, freq, pclk);
+
+        // The prescaler selected by `pre_init` divides pclk by 2 << br
+        let br = spi.spi.cr1.read().br().bits();
+        let sck_cycles = (clocks.hclk().0 / pclk.0) * (2 << br);
+        spi.into_mode(TransferModeRxOnly { sck_cycles }).init()
+    }
+}
+
+impl<SPI, SCK, MISO, MOSI, NSS> Spi<SPI, (SCK, MISO, MOSI, NSS), TransferModeNormal>
+where
+    SPI: Instance,
+    SCK: PinSck<SPI>,
+    MISO: PinMiso<SPI>,
+    MOSI: PinMosi<SPI>,
+    NSS: PinNss<SPI>,
+{
+    /// Creates an SPI slave, selected by the master through the hardware `NSS` pin.
+    ///
+    /// The clock is provided by the master, so only the `mode` has to match. Use
+    /// [`Spi::preload`] to have the first word ready before the master starts clocking.
+    pub fn new_slave(spi: SPI, pins: (SCK, MISO, MOSI, NSS), mode: Mode) -> Self {
+        unsafe {
+            // NOTE(unsafe) this reference will only be used for atomic writes with no side effects.
+            let rcc = &(*RCC::ptr());
+            SPI::enable(rcc);
+            SPI::reset(rcc);
+        }
+
+        Spi {
+            spi,
+            pins,
+            transfer_mode: TransferModeNormal,
+            _word: PhantomData,
+        }
+        .pre_init_slave(mode)
+        .init()
+    }
+}
+
+impl<SPI, SCK, MISO, MOSI, NSS, WORD> Spi<SPI, (SCK, MISO, MOSI, NSS), TransferModeNormal, WORD>
+where
+    SPI: Instance,
+    NSS: PinNss<SPI>,
+    WORD: FrameSize,
+{
+    /// Writes the first word to send into the data register.
+    ///
+    /// In slave mode the word is shifted out on the first clock edges from the master, so this
+    /// should be called while the slave is not selected.
+    pub fn preload(&mut self, word: WORD) -> nb::Result<(), Error> {
+        self.check_send(word)
+    }
+}
+
+impl<SPI, PINS, WORD> Spi<SPI, PINS, TransferModeNormal, WORD>
 where
     SPI: Instance,
 {
@@ -488,6 +679,8 @@ where
                 .clear_bit()
                 .bidioe()
                 .clear_bit()
+                .rxonly()
+                .clear_bit()
                 // spe: enable the SPI bus
                 .spe()
                 .set_bit()
@@ -497,7 +690,7 @@ where
     }
 }
 
-impl<SPI, PINS> Spi<SPI, PINS, TransferModeBidi>
+impl<SPI, PINS, WORD> Spi<SPI, PINS, TransferModeBidi, WORD>
 where
     SPI: Instance,
 {
@@ -508,6 +701,8 @@ where
                 .set_bit()
                 .bidioe()
                 .set_bit()
+                .rxonly()
+                .clear_bit()
                 // spe: enable the SPI bus
                 .spe()
                 .set_bit()
@@ -517,22 +712,150 @@ where
     }
 }
 
-impl<SPI, PINS, TRANSFER_MODE> Spi<SPI, PINS, TRANSFER_MODE>
+impl<SPI, PINS, WORD> Spi<SPI, PINS, TransferModeRxOnly, WORD>
+where
+    SPI: Instance,
+{
+    pub fn init(self) -> Self {
+        self.spi.cr1.modify(|_, w| {
+            // bidimode: 2-line unidirectional
+            w.bidimode()
+                .clear_bit()
+                .bidioe()
+                .clear_bit()
+                // rxonly: output disabled
+                .rxonly()
+                .set_bit()
+                // spe: the clock starts as soon as the SPI is enabled, leave it disabled
+                .spe()
+                .clear_bit()
+        });
+
+        self
+    }
+}
+
+impl<SPI, PINS, WORD> Spi<SPI, PINS, TransferModeRxOnly, WORD>
+where
+    SPI: Instance,
+    WORD: FrameSize,
+{
+    /// Receives `words.len()` words.
+    ///
+    /// The SPI is only enabled, and the clock generated, during the call. It is disabled following
+    /// the procedure of the reference manual, so no extra frame is clocked in.
+    pub fn receive(&mut self, words: &mut [WORD]) -> Result<(), Error> {
+        let (last, rest) = match words.split_last_mut() {
+            Some(split) => split,
+            None => return Ok(()),
+        };
+
+        self.clear_rx();
+        let result = cortex_m::interrupt::free(|_| {
+            self.spi.cr1.modify(|_, w| w.spe().set_bit());
+
+            for word in rest.iter_mut() {
+                *word = nb::block!(self.check_read())?;
+            }
+
+            // The last frame is now being received, stop the clock after it
+            cortex_m::asm::delay(self.transfer_mode.sck_cycles);
+            self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+            Ok(())
+        });
+        if let Err(e) = result {
+            self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+            return Err(e);
+        }
+
+        *last = nb::block!(self.check_read())?;
+        Ok(())
+    }
+}
+
+impl<SPI, PINS, TRANSFER_MODE> Spi<SPI, PINS, TRANSFER_MODE, u8>
 where
     SPI: Instance,
+{
+    /// Switch to 16 bit frames.
+    pub fn frame_size_16bit(self) -> Spi<SPI, PINS, TRANSFER_MODE, u16> {
+        self.into_frame_size()
+    }
+}
+
+impl<SPI, PINS, TRANSFER_MODE> Spi<SPI, PINS, TRANSFER_MODE, u16>
+where
+    SPI: Instance,
+{
+    /// Switch to 8 bit frames.
+    pub fn frame_size_8bit(self) -> Spi<SPI, PINS, TRANSFER_MODE, u8> {
+        self.into_frame_size()
+    }
+}
+
+impl<SPI, PINS, TRANSFER_MODE, WORD> Spi<SPI, PINS, TRANSFER_MODE, WORD>
+where
+    SPI: Instance,
+    WORD: FrameSize,
 {
     /// Convert the spi to another transfer mode.
     fn into_mode<TRANSFER_MODE2>(
         self,
         transfer_mode: TRANSFER_MODE2,
-    ) -> Spi<SPI, PINS, TRANSFER_MODE2> {
+    ) -> Spi<SPI, PINS, TRANSFER_MODE2, WORD> {
         Spi {
             spi: self.spi,
             pins: self.pins,
             transfer_mode,
+            _word: PhantomData,
+        }
+    }
+
+    /// Convert the spi to another frame size.
+    fn into_frame_size<WORD2: FrameSize>(mut self) -> Spi<SPI, PINS, TRANSFER_MODE, WORD2> {
+        // dff can only be changed while the SPI is disabled
+        self.while_disabled(|spi| spi.cr1.modify(|_, w| w.dff().bit(WORD2::DFF)));
+
+        Spi {
+            spi: self.spi,
+            pins: self.pins,
+            transfer_mode: self.transfer_mode,
+            _word: PhantomData,
+        }
+    }
+
+    /// Runs `f` with the SPI disabled, then enables it again if it was enabled before.
+    fn while_disabled<F>(&mut self, f: F)
+    where
+        F: FnOnce(&spi1::RegisterBlock),
+    {
+        let enabled = self.spi.cr1.read().spe().bit_is_set();
+        self.spi.cr1.modify(|_, w| w.spe().clear_bit());
+        f(&self.spi);
+        if enabled {
+            self.spi.cr1.modify(|_, w| w.spe().set_bit());
         }
     }
 
+    /// Set the order in which the bits of a frame are sent and received.
+    pub fn set_bit_order(&mut self, order: BitOrder) {
+        self.while_disabled(|spi| {
+            spi.cr1
+                .modify(|_, w| w.lsbfirst().bit(order == BitOrder::LsbFirst))
+        });
+    }
+
+    /// Set the frame format, Motorola (the default) or TI.
+    ///
+    /// In TI mode the clock polarity and phase, the bit order and the NSS management are fixed by
+    /// the hardware.
+    pub fn set_frame_format(&mut self, format: FrameFormat) {
+        self.while_disabled(|spi| {
+            spi.cr2
+                .modify(|_, w| w.frf().bit(format == FrameFormat::Ti))
+        });
+    }
+
     /// Enable/disable spi
     pub fn enable(&mut self, enable: bool) {
         self.spi.cr1.modify(|_, w| {
@@ -587,6 +910,35 @@ where
         self
     }
 
+    /// Pre initializing the SPI bus in slave mode with hardware NSS management.
+    fn pre_init_slave(self, mode: Mode) -> Self {
+        // NSS is an input in slave mode
+        self.spi.cr2.write(|w| w.ssoe().clear_bit());
+
+        self.spi.cr1.write(|w| {
+            w.cpha()
+                .bit(mode.phase == Phase::CaptureOnSecondTransition)
+                .cpol()
+                .bit(mode.polarity == Polarity::IdleHigh)
+                // mstr: slave configuration
+                .mstr()
+                .clear_bit()
+                // lsbfirst: MSB first
+                .lsbfirst()
+                .clear_bit()
+                // ssm: NSS pin input selects the slave
+                .ssm()
+                .clear_bit()
+                .rxonly()
+                .clear_bit()
+                // dff: 8 bit frames
+                .dff()
+                .clear_bit()
+        });
+
+        self
+    }
+
     /// Enable interrupts for the given `event`:
     ///  - Received data ready to be read (RXNE)
     ///  - Transmit data register empty (TXE)
@@ -635,6 +987,72 @@ where
         self.spi.sr.read().ovr().bit_is_set()
     }
 
+    /// Enable the hardware CRC calculation with the given `polynomial`, e.g. `0x09` for CRC7 with
+    /// 8 bit frames or `0x1021` for CRC16-CCITT with 16 bit frames.
+    ///
+    /// The CRC is sent and checked by [`Spi::transfer_with_crc`] and [`Spi::write_with_crc`],
+    /// or after a DMA transfer by [`Spi::check_crc`].
+    pub fn enable_crc(&mut self, polynomial: u16) {
+        // crcen can only be changed while the SPI is disabled
+        self.while_disabled(|spi| {
+            spi.crcpr.write(|w| w.crcpoly().bits(polynomial));
+            spi.cr1.modify(|_, w| w.crcen().set_bit());
+        });
+    }
+
+    /// Disable the hardware CRC calculation.
+    pub fn disable_crc(&mut self) {
+        self.while_disabled(|spi| spi.cr1.modify(|_, w| w.crcen().clear_bit()));
+    }
+
+    /// Return `true` if the CRC calculation is enabled.
+    pub fn is_crc_enabled(&self) -> bool {
+        self.spi.cr1.read().crcen().bit_is_set()
+    }
+
+    /// Return the CRC calculated over the received words since the last reset.
+    pub fn rx_crc(&self) -> u16 {
+        self.spi.rxcrcr.read().rx_crc().bits()
+    }
+
+    /// Return the CRC calculated over the sent words since the last reset.
+    pub fn tx_crc(&self) -> u16 {
+        self.spi.txcrcr.read().tx_crc().bits()
+    }
+
+    /// Reset the CRC calculation, the SPI must be idle.
+    fn reset_crc(&mut self) {
+        self.while_disabled(|spi| {
+            spi.cr1.modify(|_, w| w.crcen().clear_bit());
+            spi.cr1.modify(|_, w| w.crcen().set_bit());
+        });
+    }
+
+    /// Reads DR and SR to drop a received word and clear the OVR flag.
+    fn clear_rx(&self) {
+        let _ = self.spi.dr.read();
+        let _ = self.spi.sr.read();
+    }
+
+    /// Waits until the last frame has been shifted out.
+    fn wait_idle(&self) {
+        while self.spi.sr.read().txe().bit_is_clear() {}
+        while self.spi.sr.read().bsy().bit_is_set() {}
+    }
+
+    /// Reads the received CRC word, then checks and clears the CRCERR flag.
+    fn receive_crc(&mut self) -> Result<(), Error> {
+        while self.spi.sr.read().rxne().bit_is_clear() {}
+        let _ = self.read_data_reg();
+
+        if self.spi.sr.read().crcerr().bit_is_set() {
+            self.spi.sr.modify(|_, w| w.crcerr().clear_bit());
+            Err(Error::Crc)
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn release(self) -> (SPI, PINS) {
         (self.spi, self.pins)
     }
@@ -645,7 +1063,7 @@ where
     }
 
     #[inline(always)]
-    fn check_read(&mut self) -> nb::Result<u8, Error> {
+    fn check_read(&mut self) -> nb::Result<WORD, Error> {
         let sr = self.spi.sr.read();
 
         Err(if sr.ovr().bit_is_set() {
@@ -655,14 +1073,14 @@ where
         } else if sr.crcerr().bit_is_set() {
             Error::Crc.into()
         } else if sr.rxne().bit_is_set() {
-            return Ok(self.read_u8());
+            return Ok(self.read_data_reg());
         } else {
             nb::Error::WouldBlock
         })
     }
 
     #[inline(always)]
-    fn check_send(&mut self, byte: u8) -> nb::Result<(), Error> {
+    fn check_send(&mut self, byte: WORD) -> nb::Result<(), Error> {
         let sr = self.spi.sr.read();
 
         Err(if sr.ovr().bit_is_set() {
@@ -681,7 +1099,7 @@ where
             });
             Error::Crc.into()
         } else if sr.txe().bit_is_set() {
-            self.send_u8(byte);
+            self.write_data_reg(byte);
             return Ok(());
         } else {
             nb::Error::WouldBlock
@@ -689,141 +1107,699 @@ where
     }
 
     #[inline(always)]
-    fn read_u8(&mut self) -> u8 {
-        // NOTE(read_volatile) read only 1 byte (the svd2rust API only allows reading a half-word)
-        unsafe { ptr::read_volatile(&self.spi.dr as *const _ as *const u8) }
+    fn read_data_reg(&mut self) -> WORD {
+        // NOTE(read_volatile) read only 1 byte in 8 bit mode (the svd2rust API only allows reading
+        // a half-word)
+        unsafe { ptr::read_volatile(&self.spi.dr as *const _ as *const WORD) }
     }
 
     #[inline(always)]
-    fn send_u8(&mut self, byte: u8) {
+    fn write_data_reg(&mut self, word: WORD) {
         // NOTE(write_volatile) see note above
-        unsafe { ptr::write_volatile(&self.spi.dr as *const _ as *mut u8, byte) }
+        unsafe { ptr::write_volatile(&self.spi.dr as *const _ as *mut WORD, word) }
     }
 }
 
-impl<SPI, PINS> spi::FullDuplex<u8> for Spi<SPI, PINS, TransferModeNormal>
+impl<SPI, PINS, WORD> Spi<SPI, PINS, TransferModeNormal, WORD>
 where
     SPI: Instance,
+    WORD: FrameSize,
+{
+    /// Like the blocking `Transfer`, followed by the exchange of the CRC words.
+    ///
+    /// Returns `Error::Crc` if the received CRC does not match the received words. The CRC
+    /// calculation is reset before the transfer, it must be enabled with [`Spi::enable_crc`].
+    pub fn transfer_with_crc<'w>(&mut self, words: &'w mut [WORD]) -> Result<&'w [WORD], Error> {
+        if words.is_empty() {
+            return Ok(words);
+        }
+        self.reset_crc();
+
+        let last = words.len() - 1;
+        for (i, word) in words.iter_mut().enumerate() {
+            nb::block!(self.check_send(*word))?;
+            if i == last {
+                // Send the CRC right after the last word
+                self.spi.cr1.modify(|_, w| w.crcnext().set_bit());
+            }
+            *word = nb::block!(self.check_read())?;
+        }
+
+        self.receive_crc()?;
+        Ok(words)
+    }
+
+    /// Like the blocking `Write`, followed by sending the CRC word.
+    ///
+    /// The words received meanwhile are discarded, so no CRC error is reported. The CRC
+    /// calculation is reset before the transfer, it must be enabled with [`Spi::enable_crc`].
+    pub fn write_with_crc(&mut self, words: &[WORD]) -> Result<(), Error> {
+        if words.is_empty() {
+            return Ok(());
+        }
+        self.reset_crc();
+
+        let last = words.len() - 1;
+        for (i, word) in words.iter().enumerate() {
+            nb::block!(self.check_send(*word))?;
+            if i == last {
+                // Send the CRC right after the last word
+                self.spi.cr1.modify(|_, w| w.crcnext().set_bit());
+            }
+            nb::block!(self.check_read())?;
+        }
+
+        let _ = self.receive_crc();
+        Ok(())
+    }
+
+    /// Receives the CRC word that follows a full-duplex DMA transfer and checks it.
+    ///
+    /// With CRC enabled, the DMA transfer sends the CRC word after the last word of the buffer on
+    /// its own. Call this after [`DmaTransfer::wait`], it returns `Error::Crc` if the received CRC
+    /// does not match the received words or if no CRC word was received, then resets the CRC
+    /// calculation for the next transfer.
+    ///
+    /// Not needed after [`DmaWrite`], which discards the received words and their CRC.
+    pub fn check_crc(&mut self) -> Result<(), Error> {
+        // Don't wait for a CRC word that will never come, e.g. if the transfer was stopped
+        let result = loop {
+            let sr = self.spi.sr.read();
+            if sr.rxne().bit_is_set() {
+                break self.receive_crc();
+            }
+            if sr.txe().bit_is_set() && sr.bsy().bit_is_clear() {
+                if self.spi.sr.read().rxne().bit_is_set() {
+                    break self.receive_crc();
+                }
+                break Err(Error::Crc);
+            }
+        };
+        self.wait_idle();
+        self.reset_crc();
+        result
+    }
+}
+
+impl<SPI, PINS, WORD> spi::FullDuplex<WORD> for Spi<SPI, PINS, TransferModeNormal, WORD>
+where
+    SPI: Instance,
+    WORD: FrameSize,
 {
     type Error = Error;
 
-    fn read(&mut self) -> nb::Result<u8, Error> {
+    fn read(&mut self) -> nb::Result<WORD, Error> {
         self.check_read()
     }
 
-    fn send(&mut self, byte: u8) -> nb::Result<(), Error> {
+    fn send(&mut self, byte: WORD) -> nb::Result<(), Error> {
         self.check_send(byte)
     }
 }
 
-impl<SPI, PINS> spi::FullDuplex<u8> for Spi<SPI, PINS, TransferModeBidi>
+impl<SPI, PINS, WORD> spi::FullDuplex<WORD> for Spi<SPI, PINS, TransferModeBidi, WORD>
 where
     SPI: Instance,
+    WORD: FrameSize,
 {
     type Error = Error;
 
-    fn read(&mut self) -> nb::Result<u8, Error> {
+    fn read(&mut self) -> nb::Result<WORD, Error> {
         self.spi.cr1.modify(|_, w| w.bidioe().clear_bit());
         self.check_read()
     }
 
-    fn send(&mut self, byte: u8) -> nb::Result<(), Error> {
+    fn send(&mut self, byte: WORD) -> nb::Result<(), Error> {
         self.spi.cr1.modify(|_, w| w.bidioe().set_bit());
         self.check_send(byte)
     }
 }
 
-impl<SPI, PINS, TRANSFER_MODE> embedded_hal::blocking::spi::Transfer<u8>
-    for Spi<SPI, PINS, TRANSFER_MODE>
+// Implemented separately for each frame size, a generic implementation would conflict with the
+// `Default` blanket implementations of embedded-hal
+macro_rules! blocking {
+    ($($W:ty),+) => {
+        $(
+            impl<SPI, PINS, TRANSFER_MODE> embedded_hal::blocking::spi::Transfer<$W>
+                for Spi<SPI, PINS, TRANSFER_MODE, $W>
+            where
+                Self: spi::FullDuplex<$W>,
+                SPI: Instance,
+            {
+                type Error = <Self as spi::FullDuplex<$W>>::Error;
+
+                fn transfer<'w>(&mut self, words: &'w mut [$W]) -> Result<&'w [$W], Self::Error> {
+                    use spi::FullDuplex;
+                    for word in words.iter_mut() {
+                        nb::block!(self.send(*word))?;
+                        *word = nb::block!(self.read())?;
+                    }
+
+                    Ok(words)
+                }
+            }
+
+            impl<SPI, PINS> embedded_hal::blocking::spi::Write<$W>
+                for Spi<SPI, PINS, TransferModeNormal, $W>
+            where
+                Self: spi::FullDuplex<$W>,
+                SPI: Instance,
+            {
+                type Error = <Self as spi::FullDuplex<$W>>::Error;
+
+                fn write(&mut self, words: &[$W]) -> Result<(), Self::Error> {
+                    use spi::FullDuplex;
+                    for word in words {
+                        nb::block!(self.send(*word))?;
+                        nb::block!(self.read())?;
+                    }
+
+                    Ok(())
+                }
+            }
+
+            impl<SPI, PINS> embedded_hal::blocking::spi::Write<$W>
+                for Spi<SPI, PINS, TransferModeBidi, $W>
+            where
+                Self: spi::FullDuplex<$W>,
+                SPI: Instance,
+            {
+                type Error = <Self as spi::FullDuplex<$W>>::Error;
+
+                fn write(&mut self, words: &[$W]) -> Result<(), Self::Error> {
+                    use spi::FullDuplex;
+                    for word in words {
+                        nb::block!(self.send(*word))?;
+                    }
+
+                    Ok(())
+                }
+            }
+
+            impl<SPI, PINS> embedded_hal::blocking::spi::WriteIter<$W>
+                for Spi<SPI, PINS, TransferModeNormal, $W>
+            where
+                Self: spi::FullDuplex<$W>,
+                SPI: Instance,
+            {
+                type Error = <Self as spi::FullDuplex<$W>>::Error;
+
+                fn write_iter<WI>(&mut self, words: WI) -> Result<(), Self::Error>
+                where
+                    WI: IntoIterator<Item = $W>,
+                {
+                    use spi::FullDuplex;
+                    for word in words.into_iter() {
+                        nb::block!(self.send(word))?;
+                        nb::block!(self.read())?;
+                    }
+
+                    Ok(())
+                }
+            }
+
+            impl<SPI, PINS> embedded_hal::blocking::spi::WriteIter<$W>
+                for Spi<SPI, PINS, TransferModeBidi, $W>
+            where
+                Self: spi::FullDuplex<$W>,
+                SPI: Instance,
+            {
+                type Error = <Self as spi::FullDuplex<$W>>::Error;
+
+                fn write_iter<WI>(&mut self, words: WI) -> Result<(), Self::Error>
+                where
+                    WI: IntoIterator<Item = $W>,
+                {
+                    use spi::FullDuplex;
+                    for word in words.into_iter() {
+                        nb::block!(self.send(word))?;
+                    }
+
+                    Ok(())
+                }
+            }
+        )+
+    };
+}
+
+blocking!(u8, u16);
+
+/// SPI transmitter, used as the peripheral side of a memory to peripheral DMA transfer
+pub struct Tx<SPI, WORD = u8> {
+    _spi: PhantomData<SPI>,
+    _word: PhantomData<WORD>,
+}
+
+/// SPI receiver, used as the peripheral side of a peripheral to memory DMA transfer
+pub struct Rx<SPI, WORD = u8> {
+    _spi: PhantomData<SPI>,
+    _word: PhantomData<WORD>,
+}
+
+unsafe impl<SPI, WORD> PeriAddress for Tx<SPI, WORD>
+where
+    SPI: Instance,
+    WORD: FrameSize,
+{
+    #[inline(always)]
+    fn address(&self) -> u32 {
+        &(unsafe { &(*SPI::ptr()) }.dr) as *const _ as u32
+    }
+
+    type MemSize = WORD;
+}
+
+unsafe impl<SPI, WORD> PeriAddress for Rx<SPI, WORD>
 where
-    Self: spi::FullDuplex<u8>,
     SPI: Instance,
+    WORD: FrameSize,
 {
-    type Error = <Self as spi::FullDuplex<u8>>::Error;
+    #[inline(always)]
+    fn address(&self) -> u32 {
+        &(unsafe { &(*SPI::ptr()) }.dr) as *const _ as u32
+    }
+
+    type MemSize = WORD;
+}
 
-    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
-        use spi::FullDuplex;
-        for word in words.iter_mut() {
-            nb::block!(self.send(*word))?;
-            *word = nb::block!(self.read())?;
+/// DMA is available for the SPI transmitter based on the mappings for the SPI peripheral
+unsafe impl<SPI, WORD, STREAM, const CHANNEL: u8> DMASet<STREAM, MemoryToPeripheral, CHANNEL>
+    for Tx<SPI, WORD>
+where
+    SPI: DMASet<STREAM, MemoryToPeripheral, CHANNEL>,
+{
+}
+
+/// DMA is available for the SPI receiver based on the mappings for the SPI peripheral
+unsafe impl<SPI, WORD, STREAM, const CHANNEL: u8> DMASet<STREAM, PeripheralToMemory, CHANNEL>
+    for Rx<SPI, WORD>
+where
+    SPI: DMASet<STREAM, PeripheralToMemory, CHANNEL>,
+{
+}
+
+/// Full-duplex DMA transfer started with [`Spi::dma_transfer`]
+pub struct DmaTransfer<
+    SPI,
+    PINS,
+    WORD,
+    TXSTREAM,
+    RXSTREAM,
+    TXBUF,
+    RXBUF,
+    const TXC: u8,
+    const RXC: u8,
+> where
+    SPI: Instance,
+    WORD: FrameSize,
+    TXSTREAM: Stream,
+    RXSTREAM: Stream,
+{
+    spi: Spi<SPI, PINS, TransferModeNormal, WORD>,
+    tx: Transfer<TXSTREAM, Tx<SPI, WORD>, MemoryToPeripheral, TXBUF, TXC>,
+    rx: Transfer<RXSTREAM, Rx<SPI, WORD>, PeripheralToMemory, RXBUF, RXC>,
+}
+
+/// Transmit-only DMA transfer started with [`Spi::dma_write`]
+pub struct DmaWrite<SPI, PINS, WORD, TXSTREAM, TXBUF, const TXC: u8>
+where
+    SPI: Instance,
+    WORD: FrameSize,
+    TXSTREAM: Stream,
+{
+    spi: Spi<SPI, PINS, TransferModeNormal, WORD>,
+    tx: Transfer<TXSTREAM, Tx<SPI, WORD>, MemoryToPeripheral, TXBUF, TXC>,
+}
+
+impl<SPI, PINS, WORD> Spi<SPI, PINS, TransferModeNormal, WORD>
+where
+    SPI: Instance,
+    WORD: FrameSize,
+{
+    /// Starts a full-duplex DMA transfer, sending `tx_buf` while receiving into `rx_buf`.
+    ///
+    /// Both buffers must have the same length. The receive stream is enabled before the transmit
+    /// stream, so no received word can be lost. The transfer is complete when the last word has
+    /// been received, use [`DmaTransfer::wait`] to get back the buffers and the `Spi`.
+    ///
+    /// # Panics
+    ///
+    /// * When `tx_buf` and `rx_buf` have different lengths.
+    pub fn dma_transfer<TXSTREAM, RXSTREAM, TXBUF, RXBUF, const TXC: u8, const RXC: u8>(
+        mut self,
+        tx_stream: TXSTREAM,
+        rx_stream: RXSTREAM,
+        tx_buf: TXBUF,
+        mut rx_buf: RXBUF,
+    ) -> DmaTransfer<SPI, PINS, WORD, TXSTREAM, RXSTREAM, TXBUF, RXBUF, TXC, RXC>
+    where
+        TXSTREAM: Stream,
+        RXSTREAM: Stream,
+        ChannelX<TXC>: Channel,
+        ChannelX<RXC>: Channel,
+        Tx<SPI, WORD>: DMASet<TXSTREAM, MemoryToPeripheral, TXC>,
+        Rx<SPI, WORD>: DMASet<RXSTREAM, PeripheralToMemory, RXC>,
+        TXBUF: StaticReadBuffer<Word = WORD>,
+        RXBUF: StaticWriteBuffer<Word = WORD>,
+    {
+        // NOTE(unsafe) only the lengths are used here, the buffers are owned by the transfers
+        let (_, tx_len) = unsafe { tx_buf.read_buffer() };
+        let (_, rx_len) = unsafe { rx_buf.write_buffer() };
+        assert_eq!(tx_len, rx_len, "Buffers must have the same length");
+
+        let tx = Tx {
+            _spi: PhantomData,
+            _word: PhantomData,
+        };
+        let rx = Rx {
+            _spi: PhantomData,
+            _word: PhantomData,
+        };
+        let mut tx = Transfer::init_memory_to_peripheral(
+            tx_stream,
+            tx,
+            tx_buf,
+            None,
+            DmaConfig::default().memory_increment(true),
+        );
+        let mut rx = Transfer::init_peripheral_to_memory(
+            rx_stream,
+            rx,
+            rx_buf,
+            None,
+            DmaConfig::default()
+                .memory_increment(true)
+                .transfer_complete_interrupt(true),
+        );
+
+        // Drop any stale word, it would be the first one written by the DMA otherwise
+        self.clear_rx();
+        if self.is_crc_enabled() {
+            self.reset_crc();
         }
 
-        Ok(words)
+        self.spi.cr2.modify(|_, w| w.rxdmaen().set_bit());
+        rx.start(|_| {});
+        tx.start(|_| {});
+        self.spi.cr2.modify(|_, w| w.txdmaen().set_bit());
+
+        DmaTransfer { spi: self, tx, rx }
+    }
+
+    /// Starts a DMA transfer that only sends `tx_buf`, received data is discarded.
+    ///
+    /// With CRC enabled, the CRC calculation is reset before the transfer and the CRC word is sent
+    /// after the buffer. The received CRC is discarded with the data, no check is needed.
+    ///
+    /// Use [`DmaWrite::wait`] to get back the buffer and the `Spi`.
+    pub fn dma_write<TXSTREAM, TXBUF, const TXC: u8>(
+        mut self,
+        tx_stream: TXSTREAM,
+        tx_buf: TXBUF,
+    ) -> DmaWrite<SPI, PINS, WORD, TXSTREAM, TXBUF, TXC>
+    where
+        TXSTREAM: Stream,
+        ChannelX<TXC>: Channel,
+        Tx<SPI, WORD>: DMASet<TXSTREAM, MemoryToPeripheral, TXC>,
+        TXBUF: StaticReadBuffer<Word = WORD>,
+    {
+        let tx = Tx {
+            _spi: PhantomData,
+            _word: PhantomData,
+        };
+        let mut tx = Transfer::init_memory_to_peripheral(
+            tx_stream,
+            tx,
+            tx_buf,
+            None,
+            DmaConfig::default()
+                .memory_increment(true)
+                .transfer_complete_interrupt(true),
+        );
+
+        if self.is_crc_enabled() {
+            self.reset_crc();
+        }
+        tx.start(|_| {});
+        self.spi.cr2.modify(|_, w| w.txdmaen().set_bit());
+
+        DmaWrite { spi: self, tx }
     }
 }
 
-impl<SPI, PINS> embedded_hal::blocking::spi::Write<u8> for Spi<SPI, PINS, TransferModeNormal>
+impl<SPI, PINS, WORD, TXSTREAM, RXSTREAM, TXBUF, RXBUF, const TXC: u8, const RXC: u8>
+    DmaTransfer<SPI, PINS, WORD, TXSTREAM, RXSTREAM, TXBUF, RXBUF, TXC, RXC>
 where
-    Self: spi::FullDuplex<u8>,
     SPI: Instance,
+    WORD: FrameSize,
+    TXSTREAM: Stream,
+    RXSTREAM: Stream,
+    ChannelX<TXC>: Channel,
+    ChannelX<RXC>: Channel,
+    Tx<SPI, WORD>: DMASet<TXSTREAM, MemoryToPeripheral, TXC>,
+    Rx<SPI, WORD>: DMASet<RXSTREAM, PeripheralToMemory, RXC>,
 {
-    type Error = <Self as spi::FullDuplex<u8>>::Error;
+    /// Returns `true` once the last word has been received.
+    pub fn is_complete(&self) -> bool {
+        RXSTREAM::get_transfer_complete_flag()
+    }
 
-    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
-        use spi::FullDuplex;
-        for word in words {
-            nb::block!(self.send(*word))?;
-            nb::block!(self.read())?;
-        }
+    /// Blocks until the transfer is complete, then returns the `Spi`, the streams and the buffers.
+    pub fn wait(
+        self,
+    ) -> (
+        Spi<SPI, PINS, TransferModeNormal, WORD>,
+        TXSTREAM,
+        RXSTREAM,
+        TXBUF,
+        RXBUF,
+    ) {
+        while !self.is_complete() {}
+        self.release()
+    }
 
-        Ok(())
+    /// Stops the transfer, even if it is not complete, and returns the `Spi`, the streams and the
+    /// buffers.
+    pub fn release(
+        self,
+    ) -> (
+        Spi<SPI, PINS, TransferModeNormal, WORD>,
+        TXSTREAM,
+        RXSTREAM,
+        TXBUF,
+        RXBUF,
+    ) {
+        let DmaTransfer { spi, tx, rx } = self;
+        let (tx_stream, _, tx_buf, _) = tx.release();
+        let (rx_stream, _, rx_buf, _) = rx.release();
+        spi.spi
+            .cr2
+            .modify(|_, w| w.txdmaen().clear_bit().rxdmaen().clear_bit());
+        (spi, tx_stream, rx_stream, tx_buf, rx_buf)
     }
 }
 
-impl<SPI, PINS> embedded_hal::blocking::spi::Write<u8> for Spi<SPI, PINS, TransferModeBidi>
+impl<SPI, PINS, WORD, TXSTREAM, TXBUF, const TXC: u8>
+    DmaWrite<SPI, PINS, WORD, TXSTREAM, TXBUF, TXC>
 where
-    Self: spi::FullDuplex<u8>,
     SPI: Instance,
+    WORD: FrameSize,
+    TXSTREAM: Stream,
+    ChannelX<TXC>: Channel,
+    Tx<SPI, WORD>: DMASet<TXSTREAM, MemoryToPeripheral, TXC>,
 {
-    type Error = <Self as spi::FullDuplex<u8>>::Error;
+    /// Returns `true` once the last word has been handed to the SPI.
+    ///
+    /// The word may still be shifting out, [`DmaWrite::wait`] takes care of that.
+    pub fn is_complete(&self) -> bool {
+        TXSTREAM::get_transfer_complete_flag()
+    }
 
-    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
-        use spi::FullDuplex;
-        for word in words {
-            nb::block!(self.send(*word))?;
-        }
+    /// Blocks until the last word has been sent, then returns the `Spi`, the stream and the buffer.
+    pub fn wait(self) -> (Spi<SPI, PINS, TransferModeNormal, WORD>, TXSTREAM, TXBUF) {
+        while !self.is_complete() {}
+        self.spi.wait_idle();
+        self.release()
+    }
 
-        Ok(())
+    /// Stops the transfer, even if it is not complete, and returns the `Spi`, the stream and the
+    /// buffer.
+    pub fn release(self) -> (Spi<SPI, PINS, TransferModeNormal, WORD>, TXSTREAM, TXBUF) {
+        let DmaWrite { mut spi, tx } = self;
+        let (tx_stream, _, tx_buf, _) = tx.release();
+        spi.spi.cr2.modify(|_, w| w.txdmaen().clear_bit());
+        if spi.is_crc_enabled() {
+            // The CRC word is sent after the buffer, the received one is discarded with the data
+            spi.wait_idle();
+            spi.clear_rx();
+            spi.spi.sr.modify(|_, w| w.crcerr().clear_bit());
+            spi.reset_crc();
+        }
+        // Received words were not read, clear the overrun they caused
+        spi.clear_rx();
+        (spi, tx_stream, tx_buf)
     }
 }
 
-impl<SPI, PINS> embedded_hal::blocking::spi::WriteIter<u8> for Spi<SPI, PINS, TransferModeNormal>
+/// Interrupt driven full-duplex transfer started with [`Spi::irq_transfer`]
+///
+/// [`IrqTransfer::on_interrupt`] must be called from the interrupt handler of the SPI.
+pub struct IrqTransfer<SPI, PINS, WORD, TXBUF, RXBUF, CS>
 where
-    Self: spi::FullDuplex<u8>,
     SPI: Instance,
+    WORD: FrameSize,
 {
-    type Error = <Self as spi::FullDuplex<u8>>::Error;
+    spi: Spi<SPI, PINS, TransferModeNormal, WORD>,
+    tx_buf: TXBUF,
+    rx_buf: RXBUF,
+    cs: CS,
+    len: usize,
+    received: usize,
+    result: Option<Result<(), Error>>,
+}
 
-    fn write_iter<WI>(&mut self, words: WI) -> Result<(), Self::Error>
+impl<SPI, PINS, WORD> Spi<SPI, PINS, TransferModeNormal, WORD>
+where
+    SPI: Instance,
+    WORD: FrameSize,
+{
+    /// Starts an interrupt driven full-duplex transfer, sending `tx_buf` while receiving into
+    /// `rx_buf`.
+    ///
+    /// `cs` is driven low for the duration of the transfer, use `NoPin` if chip select is handled
+    /// elsewhere. The `Rxne` and `Error` events are enabled until the transfer ends, the SPI
+    /// interrupt must be unmasked in the NVIC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffers have different lengths.
+    pub fn irq_transfer<TXBUF, RXBUF, CS>(
+        mut self,
+        tx_buf: TXBUF,
+        mut rx_buf: RXBUF,
+        mut cs: CS,
+    ) -> IrqTransfer<SPI, PINS, WORD, TXBUF, RXBUF, CS>
     where
-        WI: IntoIterator<Item = u8>,
+        TXBUF: StaticReadBuffer<Word = WORD>,
+        RXBUF: StaticWriteBuffer<Word = WORD>,
+        CS: OutputPin,
     {
-        use spi::FullDuplex;
-        for word in words.into_iter() {
-            nb::block!(self.send(word))?;
-            nb::block!(self.read())?;
+        let (tx_ptr, len) = unsafe { tx_buf.static_read_buffer() };
+        let (_, rx_len) = unsafe { rx_buf.static_write_buffer() };
+        assert_eq!(len, rx_len);
+
+        if len == 0 {
+            return IrqTransfer {
+                spi: self,
+                tx_buf,
+                rx_buf,
+                cs,
+                len,
+                received: 0,
+                result: Some(Ok(())),
+            };
         }
 
-        Ok(())
+        self.clear_rx();
+        let _ = cs.set_low();
+        self.listen(Event::Rxne);
+        self.listen(Event::Error);
+        // One word in flight at a time, the next one is sent once this one is received
+        self.write_data_reg(unsafe { *tx_ptr });
+
+        IrqTransfer {
+            spi: self,
+            tx_buf,
+            rx_buf,
+            cs,
+            len,
+            received: 0,
+            result: None,
+        }
     }
 }
 
-impl<SPI, PINS> embedded_hal::blocking::spi::WriteIter<u8> for Spi<SPI, PINS, TransferModeBidi>
+impl<SPI, PINS, WORD, TXBUF, RXBUF, CS> IrqTransfer<SPI, PINS, WORD, TXBUF, RXBUF, CS>
 where
-    Self: spi::FullDuplex<u8>,
     SPI: Instance,
+    WORD: FrameSize,
+    TXBUF: StaticReadBuffer<Word = WORD>,
+    RXBUF: StaticWriteBuffer<Word = WORD>,
+    CS: OutputPin,
 {
-    type Error = <Self as spi::FullDuplex<u8>>::Error;
+    /// Advances the transfer, to be called from the SPI interrupt handler.
+    ///
+    /// Returns `Ok(())` once the transfer is complete and chip select has been released,
+    /// `WouldBlock` while it is in progress, or the error that stopped it.
+    pub fn on_interrupt(&mut self) -> nb::Result<(), Error> {
+        if let Some(result) = self.result {
+            return result.map_err(nb::Error::Other);
+        }
 
-    fn write_iter<WI>(&mut self, words: WI) -> Result<(), Self::Error>
-    where
-        WI: IntoIterator<Item = u8>,
-    {
-        use spi::FullDuplex;
-        for word in words.into_iter() {
-            nb::block!(self.send(word))?;
+        let word = match self.spi.check_read() {
+            Ok(word) => word,
+            Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+            Err(nb::Error::Other(e)) => {
+                self.finish(Err(e));
+                return Err(nb::Error::Other(e));
+            }
+        };
+
+        let (rx_ptr, _) = unsafe { self.rx_buf.static_write_buffer() };
+        unsafe { *rx_ptr.add(self.received) = word };
+        self.received += 1;
+
+        if self.received < self.len {
+            let (tx_ptr, _) = unsafe { self.tx_buf.static_read_buffer() };
+            self.spi
+                .write_data_reg(unsafe { *tx_ptr.add(self.received) });
+            Err(nb::Error::WouldBlock)
+        } else {
+            self.finish(Ok(()));
+            Ok(())
         }
+    }
 
-        Ok(())
+    /// Returns `true` once the transfer has ended, successfully or not.
+    pub fn is_complete(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Returns the number of words exchanged so far.
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// Stops the transfer, even if it is not complete, and returns the `Spi`, the buffers and the
+    /// chip select pin.
+    pub fn release(mut self) -> (Spi<SPI, PINS, TransferModeNormal, WORD>, TXBUF, RXBUF, CS) {
+        if self.result.is_none() {
+            self.spi.wait_idle();
+            self.finish(Ok(()));
+        }
+        let IrqTransfer {
+            spi,
+            tx_buf,
+            rx_buf,
+            cs,
+            ..
+        } = self;
+        spi.clear_rx();
+        (spi, tx_buf, rx_buf, cs)
+    }
+
+    fn finish(&mut self, result: Result<(), Error>) {
+        self.spi.unlisten(Event::Rxne);
+        self.spi.unlisten(Event::Error);
+        if result.is_err() {
+            // Clear OVR, MODF and CRCERR so the SPI can be used again
+            self.spi.clear_rx();
+            self.spi.spi.cr1.modify(|_, w| w);
+            self.spi.spi.sr.modify(|_, w| w.crcerr().clear_bit());
+        } else {
+            self.spi.wait_idle();
+        }
+        let _ = self.cs.set_high();
+        self.result = Some(result);
     }
 }
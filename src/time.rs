@@ -1,3 +1,5 @@
+use core::ops::{Add, Sub};
+
 /// Bits per second
 #[derive(PartialEq, PartialOrd, Clone, Copy, Debug)]
 pub struct Bps(pub u32);
@@ -25,8 +27,17 @@ pub trait U32Ext {
     /// Wrap in `MegaHertz`
     fn mhz(self) -> MegaHertz;
 
+    /// Wrap in `Seconds`
+    fn secs(self) -> Seconds;
+
     /// Wrap in `MilliSeconds`
     fn ms(self) -> MilliSeconds;
+
+    /// Wrap in `MicroSeconds`
+    fn us(self) -> MicroSeconds;
+
+    /// Wrap in `NanoSeconds`
+    fn ns(self) -> NanoSeconds;
 }
 
 impl U32Ext for u32 {
@@ -46,9 +57,21 @@ impl U32Ext for u32 {
         MegaHertz(self)
     }
 
+    fn secs(self) -> Seconds {
+        Seconds(self)
+    }
+
     fn ms(self) -> MilliSeconds {
         MilliSeconds(self)
     }
+
+    fn us(self) -> MicroSeconds {
+        MicroSeconds(self)
+    }
+
+    fn ns(self) -> NanoSeconds {
+        NanoSeconds(self)
+    }
 }
 
 impl From<u32> for Hertz {
@@ -57,24 +80,199 @@ impl From<u32> for Hertz {
     }
 }
 
-impl From<KiloHertz> for Hertz {
-    fn from(mhz: KiloHertz) -> Self {
-        Self(mhz.0 * 1_000)
-    }
+// The conversions to a finer unit panic on overflow instead of wrapping around
+macro_rules! convert {
+    ($msg:literal, $($From:ident => $To:ident: $factor:literal,)+) => {
+        $(
+            impl From<$From> for $To {
+                fn from(value: $From) -> Self {
+                    Self(value.0.checked_mul($factor).expect($msg))
+                }
+            }
+        )+
+    };
 }
 
-impl From<MegaHertz> for Hertz {
-    fn from(mhz: MegaHertz) -> Self {
-        Self(mhz.0 * 1_000_000)
-    }
-}
+convert!(
+    "frequency overflow",
+    KiloHertz => Hertz: 1_000,
+    MegaHertz => Hertz: 1_000_000,
+    MegaHertz => KiloHertz: 1_000,
+);
 
-impl From<MegaHertz> for KiloHertz {
-    fn from(mhz: MegaHertz) -> Self {
-        Self(mhz.0 * 1_000)
+impl Hertz {
+    /// Returns the duration of one cycle, rounded down, or `None` for 0 Hz
+    pub fn to_duration(self) -> Option<NanoSeconds> {
+        1_000_000_000u32.checked_div(self.0).map(NanoSeconds)
     }
 }
 
+/// Time unit
+#[derive(PartialEq, PartialOrd, Clone, Copy, Debug)]
+pub struct Seconds(pub u32);
+
 /// Time unit
 #[derive(PartialEq, PartialOrd, Clone, Copy, Debug)]
 pub struct MilliSeconds(pub u32);
+
+/// Time unit
+#[derive(PartialEq, PartialOrd, Clone, Copy, Debug)]
+pub struct MicroSeconds(pub u32);
+
+/// Time unit
+#[derive(PartialEq, PartialOrd, Clone, Copy, Debug)]
+pub struct NanoSeconds(pub u32);
+
+convert!(
+    "duration overflow",
+    Seconds => MilliSeconds: 1_000,
+    Seconds => MicroSeconds: 1_000_000,
+    Seconds => NanoSeconds: 1_000_000_000,
+    MilliSeconds => MicroSeconds: 1_000,
+    MilliSeconds => NanoSeconds: 1_000_000,
+    MicroSeconds => NanoSeconds: 1_000,
+);
+
+macro_rules! duration {
+    ($($Duration:ident: $nanos:literal,)+) => {
+        $(
+            impl $Duration {
+                /// Returns the sum, or `None` on overflow
+                pub fn checked_add(self, rhs: Self) -> Option<Self> {
+                    self.0.checked_add(rhs.0).map(Self)
+                }
+
+                /// Returns the difference, or `None` if `rhs` is longer
+                pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+                    self.0.checked_sub(rhs.0).map(Self)
+                }
+
+                /// Returns the duration in nanoseconds, which cannot overflow
+                pub fn as_nanos(self) -> u64 {
+                    u64::from(self.0) * $nanos
+                }
+
+                /// Returns the rate with one cycle per duration, rounded down, or `None` for a
+                /// zero duration
+                pub fn to_rate(self) -> Option<Hertz> {
+                    1_000_000_000u64
+                        .checked_div(self.as_nanos())
+                        .map(|rate| Hertz(rate as u32))
+                }
+            }
+
+            impl From<$Duration> for Period {
+                fn from(duration: $Duration) -> Self {
+                    Period::Nanos(duration.as_nanos())
+                }
+            }
+        )+
+    };
+}
+
+duration!(
+    Seconds: 1_000_000_000,
+    MilliSeconds: 1_000_000,
+    MicroSeconds: 1_000,
+    NanoSeconds: 1,
+);
+
+/// Period of a timer, given as a rate or as a duration
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Period {
+    /// Events per second
+    Rate(Hertz),
+    /// Duration between events, in nanoseconds
+    Nanos(u64),
+}
+
+impl Period {
+    /// Returns the number of `clk` cycles in the period, rounded down, or `None` for a rate of
+    /// 0 Hz or on overflow
+    pub fn cycles(self, clk: Hertz) -> Option<u64> {
+        let clk = u64::from(clk.0);
+        match self {
+            Period::Rate(rate) => clk.checked_div(u64::from(rate.0)),
+            Period::Nanos(nanos) => clk.checked_mul(nanos).map(|c| c / 1_000_000_000),
+        }
+    }
+}
+
+impl From<u32> for Period {
+    fn from(rate: u32) -> Self {
+        Period::Rate(Hertz(rate))
+    }
+}
+
+impl From<Hertz> for Period {
+    fn from(rate: Hertz) -> Self {
+        Period::Rate(rate)
+    }
+}
+
+impl From<KiloHertz> for Period {
+    fn from(rate: KiloHertz) -> Self {
+        Period::Rate(rate.into())
+    }
+}
+
+impl From<MegaHertz> for Period {
+    fn from(rate: MegaHertz) -> Self {
+        Period::Rate(rate.into())
+    }
+}
+
+/// Point in time, in microseconds since an arbitrary origin such as the start of a timer
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub struct Instant {
+    micros: u64,
+}
+
+impl Instant {
+    pub const fn from_micros(micros: u64) -> Self {
+        Self { micros }
+    }
+
+    pub const fn as_micros(self) -> u64 {
+        self.micros
+    }
+
+    /// Returns the instant `duration` later, or `None` on overflow
+    pub fn checked_add<D: Into<MicroSeconds>>(self, duration: D) -> Option<Self> {
+        self.micros
+            .checked_add(u64::from(duration.into().0))
+            .map(Self::from_micros)
+    }
+
+    /// Returns the instant `duration` earlier, or `None` if it is before the origin
+    pub fn checked_sub<D: Into<MicroSeconds>>(self, duration: D) -> Option<Self> {
+        self.micros
+            .checked_sub(u64::from(duration.into().0))
+            .map(Self::from_micros)
+    }
+
+    /// Returns the time elapsed since `earlier`, or `None` if `earlier` is later or the
+    /// duration does not fit
+    pub fn checked_duration_since(self, earlier: Self) -> Option<MicroSeconds> {
+        let micros = self.micros.checked_sub(earlier.micros)?;
+        cast::u32(micros).ok().map(MicroSeconds)
+    }
+}
+
+impl<D: Into<MicroSeconds>> Add<D> for Instant {
+    type Output = Self;
+
+    /// Panics on overflow
+    fn add(self, duration: D) -> Self {
+        self.checked_add(duration).expect("instant overflow")
+    }
+}
+
+impl<D: Into<MicroSeconds>> Sub<D> for Instant {
+    type Output = Self;
+
+    /// Panics if the result is before the origin
+    fn sub(self, duration: D) -> Self {
+        self.checked_sub(duration).expect("instant underflow")
+    }
+}
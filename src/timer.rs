@@ -3,7 +3,6 @@
 //! Pins can be used for PWM output in both push-pull mode (`Alternate`) and open-drain mode
 //! (`AlternateOD`).
 
-use cast::{u16, u32};
 use cortex_m::peripheral::syst::SystClkSource;
 use cortex_m::peripheral::{DCB, DWT, SYST};
 use embedded_hal::timer::{Cancel, CountDown, Periodic};
@@ -12,7 +11,15 @@ use void::Void;
 use crate::pac::RCC;
 
 use crate::rcc::{self, Clocks};
-use crate::time::Hertz;
+use crate::time::{Hertz, Period};
+
+mod monotonic;
+#[cfg(feature = "rtic-monotonic")]
+pub use monotonic::Mono;
+pub use monotonic::MonoTimer32;
+#[cfg(not(feature = "stm32f410"))]
+pub use monotonic::MonoTimer64;
+pub mod wheel;
 
 /// Timer wrapper
 pub struct Timer<TIM> {
@@ -28,12 +35,13 @@ pub struct CountDownTimer<TIM> {
 
 impl<TIM> Timer<TIM>
 where
-    CountDownTimer<TIM>: CountDown<Time = Hertz>,
+    CountDownTimer<TIM>: CountDown<Time = Period>,
 {
-    /// Starts timer in count down mode at a given frequency
+    /// Starts timer in count down mode at a given frequency or period, e.g. `1.hz()` or
+    /// `500.ms()`
     pub fn start_count_down<T>(self, timeout: T) -> CountDownTimer<TIM>
     where
-        T: Into<Hertz>,
+        T: Into<Period>,
     {
         let Self { tim, clk } = self;
         let mut timer = CountDownTimer { tim, clk };
@@ -45,15 +53,63 @@ where
 impl<TIM> Periodic for CountDownTimer<TIM> {}
 
 /// Interrupt events
+///
+/// Events a timer does not have are ignored, e.g. `C3` on a 2 channels timer or `Break` on a
+/// general purpose timer.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum Event {
     /// CountDownTimer timed out / count down ended
     TimeOut,
+    /// Capture/compare channel 1 matched or captured
+    C1,
+    /// Capture/compare channel 2 matched or captured
+    C2,
+    /// Capture/compare channel 3 matched or captured
+    C3,
+    /// Capture/compare channel 4 matched or captured
+    C4,
+    /// Trigger input active edge
+    Trigger,
+    /// Break input active, on advanced timers
+    Break,
+}
+
+impl Event {
+    /// Bit of the event in the DIER and SR registers
+    pub(crate) fn mask(self) -> u32 {
+        match self {
+            Event::TimeOut => 1 << 0,
+            Event::C1 => 1 << 1,
+            Event::C2 => 1 << 2,
+            Event::C3 => 1 << 3,
+            Event::C4 => 1 << 4,
+            Event::Trigger => 1 << 6,
+            Event::Break => 1 << 7,
+        }
+    }
+}
+
+/// Valid interrupt flags of a timer in the SR register, cleared by writing 0
+pub(crate) trait Flags {
+    const FLAGS: u32;
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum Error {
     /// CountDownTimer is disabled
     Disabled,
+    /// The tick rate can not be derived from the timer clock
+    TickRate,
+    /// The delay is 0 or does not fit in the counter
+    Delay,
+}
+
+/// Returns the prescaler value dividing the timer clock `clk` down to `tick_rate`
+pub(crate) fn prescaler(clk: Hertz, tick_rate: Hertz) -> Result<u16, Error> {
+    match clk.0.checked_div(tick_rate.0) {
+        Some(div) if (1..=1 << 16).contains(&div) => Ok((div - 1) as u16),
+        _ => Err(Error::TickRate),
+    }
 }
 
 impl Timer<SYST> {
@@ -74,29 +130,30 @@ impl Timer<SYST> {
 impl CountDownTimer<SYST> {
     /// Starts listening for an `event`
     pub fn listen(&mut self, event: Event) {
-        match event {
-            Event::TimeOut => self.tim.enable_interrupt(),
+        if event == Event::TimeOut {
+            self.tim.enable_interrupt()
         }
     }
 
     /// Stops listening for an `event`
     pub fn unlisten(&mut self, event: Event) {
-        match event {
-            Event::TimeOut => self.tim.disable_interrupt(),
+        if event == Event::TimeOut {
+            self.tim.disable_interrupt()
         }
     }
 }
 
 impl CountDown for CountDownTimer<SYST> {
-    type Time = Hertz;
+    type Time = Period;
 
     fn start<T>(&mut self, timeout: T)
     where
-        T: Into<Hertz>,
+        T: Into<Period>,
     {
-        let rvr = self.clk.0 / timeout.into().0 - 1;
-
-        assert!(rvr < (1 << 24));
+        let rvr = match timeout.into().cycles(self.clk) {
+            Some(cycles) if cycles > 0 && cycles <= (1 << 24) => cycles as u32 - 1,
+            _ => panic!("period out of the SysTick range"),
+        };
 
         self.tim.set_reload(rvr);
         self.tim.clear_current();
@@ -199,47 +256,311 @@ where
     }
 }
 
-macro_rules! hal {
-    ($($TIM:ty: ($tim:ident),)+) => {
+/// What a master timer sends on its trigger output (TRGO)
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum MasterMode {
+    /// The UG bit
+    Reset = 0,
+    /// The counter enable, to start slave timers with the master
+    Enable = 1,
+    /// The update event, to use the master as a prescaler for a slave timer
+    Update = 2,
+    /// A pulse on each capture or compare match of channel 1
+    ComparePulse = 3,
+    /// The channel 1 output reference
+    Compare1 = 4,
+    /// The channel 2 output reference
+    Compare2 = 5,
+    /// The channel 3 output reference
+    Compare3 = 6,
+    /// The channel 4 output reference
+    Compare4 = 7,
+}
+
+/// What a slave timer does on the trigger output of its master
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum SlaveMode {
+    /// The trigger is ignored
+    Disabled = 0,
+    /// A rising edge of the trigger resets the counter
+    ///
+    /// PWM timers reset by the `Enable` trigger of a common master run in phase once the master
+    /// is started.
+    Reset = 4,
+    /// The counter counts only while the trigger is high
+    Gated = 5,
+    /// A rising edge of the trigger starts the counter
+    Trigger = 6,
+    /// The rising edges of the trigger clock the counter
+    ExternalClock = 7,
+}
+
+/// The trigger output of `MASTER` is connected to the internal trigger input `ITR` of this timer
+pub trait InternalTrigger<MASTER>: crate::Sealed {
+    #[doc(hidden)]
+    const ITR: u8;
+}
+
+macro_rules! master {
+    ($($TIM:ty,)+) => {
         $(
-            impl Instance for $TIM { }
+            impl Timer<$TIM> {
+                /// Select what is sent on the trigger output to the slave timers
+                #[allow(unused_unsafe)]
+                pub fn set_master_mode(&mut self, mode: MasterMode) {
+                    self.tim.cr2.modify(|_, w| unsafe { w.mms().bits(mode as u8) });
+                }
+            }
+        )+
+    }
+}
 
-            impl CountDownTimer<$TIM> {
-                /// Starts listening for an `event`
+macro_rules! slave {
+    ($($TIM:ty,)+) => {
+        $(
+            impl Timer<$TIM> {
+                /// Set the slave mode, on the trigger output of `MASTER`
                 ///
-                /// Note, you will also have to enable the TIM2 interrupt in the NVIC to start
-                /// receiving events.
-                pub fn listen(&mut self, event: Event) {
-                    match event {
-                        Event::TimeOut => {
-                            // Enable update event interrupt
-                            self.tim.dier.write(|w| w.uie().set_bit());
-                        }
-                    }
+                /// `MASTER` is selected with a turbofish, e.g. `set_slave_mode::<TIM2>(..)`.
+                #[allow(unused_unsafe)]
+                pub fn set_slave_mode<MASTER>(&mut self, mode: SlaveMode)
+                where
+                    $TIM: InternalTrigger<MASTER>,
+                {
+                    let itr = <$TIM as InternalTrigger<MASTER>>::ITR;
+                    self.tim.smcr.modify(|_, w| unsafe { w.ts().bits(itr).sms().bits(mode as u8) });
                 }
 
-                /// Clears interrupt associated with `event`.
-                ///
-                /// If the interrupt is not cleared, it will immediately retrigger after
-                /// the ISR has finished.
-                pub fn clear_interrupt(&mut self, event: Event) {
-                    match event {
-                        Event::TimeOut => {
-                            // Clear interrupt flag
-                            self.tim.sr.write(|w| w.uif().clear_bit());
-                        }
-                    }
+                /// Enable/disable the master/slave mode, which delays the trigger input so this
+                /// timer and the timers it triggers start together
+                pub fn set_master_slave(&mut self, enable: bool) {
+                    self.tim.smcr.modify(|_, w| w.msm().bit(enable));
                 }
+            }
+        )+
+    }
+}
 
-                /// Stops listening for an `event`
-                pub fn unlisten(&mut self, event: Event) {
-                    match event {
-                        Event::TimeOut => {
-                            // Enable update event interrupt
-                            self.tim.dier.write(|w| w.uie().clear_bit());
-                        }
-                    }
+macro_rules! itr {
+    ($($SLAVE:ident: [$($MASTER:ident: $itr:literal),+],)+) => {
+        $(
+            $(
+                impl InternalTrigger<crate::pac::$MASTER> for crate::pac::$SLAVE {
+                    const ITR: u8 = $itr;
                 }
+            )+
+        )+
+    }
+}
+
+master!(crate::pac::TIM1, crate::pac::TIM5,);
+slave!(crate::pac::TIM1, crate::pac::TIM5, crate::pac::TIM9,);
+itr!(
+    TIM1: [TIM5: 0],
+);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+master!(crate::pac::TIM2, crate::pac::TIM3, crate::pac::TIM4,);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+slave!(crate::pac::TIM2, crate::pac::TIM3, crate::pac::TIM4,);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+itr!(
+    TIM1: [TIM2: 1, TIM3: 2, TIM4: 3],
+    TIM2: [TIM1: 0, TIM3: 2, TIM4: 3],
+    TIM3: [TIM1: 0, TIM2: 1, TIM5: 2, TIM4: 3],
+    TIM4: [TIM1: 0, TIM2: 1, TIM3: 2],
+    TIM5: [TIM2: 0, TIM3: 1, TIM4: 2],
+    TIM9: [TIM2: 0, TIM3: 1, TIM10: 2, TIM11: 3],
+);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f410",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+master!(crate::pac::TIM6,);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+master!(crate::pac::TIM7, crate::pac::TIM8,);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+slave!(crate::pac::TIM8, crate::pac::TIM12,);
+
+#[cfg(any(
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+itr!(
+    TIM2: [TIM8: 1],
+    TIM4: [TIM8: 3],
+    TIM5: [TIM8: 3],
+    TIM8: [TIM1: 0, TIM2: 1, TIM4: 2, TIM5: 3],
+    TIM12: [TIM4: 0, TIM5: 1, TIM13: 2, TIM14: 3],
+);
+
+macro_rules! events {
+    ($TIM:ty) => {
+        /// Starts listening for an `event`
+        ///
+        /// Note, you will also have to enable the timer interrupt in the NVIC to start
+        /// receiving events.
+        #[allow(unused_unsafe)]
+        pub fn listen(&mut self, event: Event) {
+            let mask = event.mask() & <$TIM as Flags>::FLAGS;
+            self.tim
+                .dier
+                .modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+        }
+
+        /// Stops listening for an `event`
+        #[allow(unused_unsafe)]
+        pub fn unlisten(&mut self, event: Event) {
+            let mask = event.mask() & <$TIM as Flags>::FLAGS;
+            self.tim
+                .dier
+                .modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        }
+
+        /// Clears interrupt associated with `event`.
+        ///
+        /// If the interrupt is not cleared, it will immediately retrigger after
+        /// the ISR has finished.
+        #[allow(unused_unsafe)]
+        pub fn clear_interrupt(&mut self, event: Event) {
+            // Writing 1 leaves the other flags unchanged
+            let flags = <$TIM as Flags>::FLAGS & !event.mask();
+            self.tim.sr.write(|w| unsafe { w.bits(flags) });
+        }
+
+        /// Returns `true` if the interrupt flag of `event` is set
+        pub fn is_pending(&self, event: Event) -> bool {
+            self.tim.sr.read().bits() & event.mask() & <$TIM as Flags>::FLAGS != 0
+        }
+    };
+}
+
+macro_rules! hal {
+    ($($TIM:ty: ($tim:ident, $flags:literal),)+) => {
+        $(
+            impl Instance for $TIM { }
+
+            impl Flags for $TIM {
+                const FLAGS: u32 = $flags;
+            }
+
+            impl Timer<$TIM> {
+                events!($TIM);
+            }
+
+            impl CountDownTimer<$TIM> {
+                events!($TIM);
 
                 /// Releases the TIM peripheral
                 pub fn release(self) -> $TIM {
@@ -250,25 +571,27 @@ macro_rules! hal {
             }
 
             impl CountDown for CountDownTimer<$TIM> {
-                type Time = Hertz;
+                type Time = Period;
 
                 fn start<T>(&mut self, timeout: T)
                 where
-                    T: Into<Hertz>,
+                    T: Into<Period>,
                 {
+                    let ticks = match timeout.into().cycles(self.clk) {
+                        Some(ticks) if ticks > 0 && ticks <= (1 << 32) => ticks,
+                        _ => panic!("period out of the timer range"),
+                    };
+
                     // pause
                     self.tim.cr1.modify(|_, w| w.cen().clear_bit());
                     // reset counter
                     self.tim.cnt.reset();
 
-                    let frequency = timeout.into().0;
-                    let ticks = self.clk.0 / frequency;
-
-                    let psc = u16((ticks - 1) / (1 << 16)).unwrap();
-                    self.tim.psc.write(|w| w.psc().bits(psc) );
+                    let psc = (ticks - 1) / (1 << 16);
+                    self.tim.psc.write(|w| w.psc().bits(psc as u16) );
 
-                    let arr = u16(ticks / u32(psc + 1)).unwrap();
-                    self.tim.arr.write(|w| unsafe { w.bits(u32(arr)) });
+                    let arr = u64::min(ticks / (psc + 1), 0xffff);
+                    self.tim.arr.write(|w| unsafe { w.bits(arr as u32) });
 
                     // Trigger update event to load the registers
                     self.tim.cr1.modify(|_, w| w.urs().set_bit());
@@ -309,10 +632,10 @@ macro_rules! hal {
 }
 
 hal! {
-    crate::pac::TIM1: (tim1),
-    crate::pac::TIM5: (tim5),
-    crate::pac::TIM9: (tim9),
-    crate::pac::TIM11: (tim11),
+    crate::pac::TIM1: (tim1, 0x1eff),
+    crate::pac::TIM5: (tim5, 0x1e5f),
+    crate::pac::TIM9: (tim9, 0x0647),
+    crate::pac::TIM11: (tim11, 0x0203),
 }
 
 #[cfg(any(
@@ -334,10 +657,10 @@ hal! {
     feature = "stm32f479"
 ))]
 hal! {
-    crate::pac::TIM2: (tim2),
-    crate::pac::TIM3: (tim3),
-    crate::pac::TIM4: (tim4),
-    crate::pac::TIM10: (tim10),
+    crate::pac::TIM2: (tim2, 0x1e5f),
+    crate::pac::TIM3: (tim3, 0x1e5f),
+    crate::pac::TIM4: (tim4, 0x1e5f),
+    crate::pac::TIM10: (tim10, 0x0203),
 }
 
 #[cfg(any(
@@ -358,7 +681,7 @@ hal! {
     feature = "stm32f479"
 ))]
 hal! {
-    crate::pac::TIM6: (tim6),
+    crate::pac::TIM6: (tim6, 0x0001),
 }
 
 #[cfg(any(
@@ -378,11 +701,17 @@ hal! {
     feature = "stm32f479"
 ))]
 hal! {
-    crate::pac::TIM7: (tim7),
-    crate::pac::TIM8: (tim8),
-    crate::pac::TIM12: (tim12),
-    crate::pac::TIM13: (tim13),
-    crate::pac::TIM14: (tim14),
+    crate::pac::TIM7: (tim7, 0x0001),
+    crate::pac::TIM8: (tim8, 0x1eff),
+    crate::pac::TIM12: (tim12, 0x0647),
+    crate::pac::TIM13: (tim13, 0x0203),
+    crate::pac::TIM14: (tim14, 0x0203),
+}
+
+// The F401 PAC declares TIM8, which `pwm_input` implements
+#[cfg(feature = "stm32f401")]
+impl Flags for crate::pac::TIM8 {
+    const FLAGS: u32 = 0x1eff;
 }
 
 #[allow(unused)]
@@ -407,6 +736,17 @@ pub trait PinC2<TIM> {}
 pub trait PinC3<TIM> {}
 pub trait PinC4<TIM> {}
 
+// Complementary output channels marker traits
+pub trait PinC1N<TIM> {}
+pub trait PinC2N<TIM> {}
+pub trait PinC3N<TIM> {}
+
+// Break input marker trait
+pub trait PinBkin<TIM> {}
+
+// External trigger input marker trait
+pub trait PinEtr<TIM> {}
+
 macro_rules! channel_impl {
     ( $( $TIM:ident, $PINC:ident, $PINX:ident, $AF:literal; )+ ) => {
         $(
@@ -422,6 +762,19 @@ channel_impl!(
     TIM1, PinC3, PA10, 1;
     TIM1, PinC4, PA11, 1;
 
+    TIM1, PinC1N, PA7, 1;
+    TIM1, PinC2N, PB0, 1;
+    TIM1, PinC3N, PB1, 1;
+
+    TIM1, PinC1N, PB13, 1;
+    TIM1, PinC2N, PB14, 1;
+    TIM1, PinC3N, PB15, 1;
+
+    TIM1, PinBkin, PA6, 1;
+    TIM1, PinBkin, PB12, 1;
+
+    TIM1, PinEtr, PA12, 1;
+
     TIM5, PinC1, PA0, 2;
     TIM5, PinC2, PA1, 2;
     TIM5, PinC3, PA2, 2;
@@ -452,6 +805,14 @@ channel_impl!(
     TIM1, PinC3, PE13, 1;
     TIM1, PinC4, PE14, 1;
 
+    TIM1, PinC1N, PE8, 1;
+    TIM1, PinC2N, PE10, 1;
+    TIM1, PinC3N, PE12, 1;
+
+    TIM1, PinBkin, PE15, 1;
+
+    TIM1, PinEtr, PE7, 1;
+
     TIM2, PinC1, PA0, 1;
     TIM2, PinC2, PA1, 1;
     TIM2, PinC3, PA2, 1;
@@ -464,6 +825,10 @@ channel_impl!(
     TIM2, PinC1, PA5, 1;
     TIM2, PinC1, PA15, 1;
 
+    TIM2, PinEtr, PA0, 1;
+    TIM2, PinEtr, PA5, 1;
+    TIM2, PinEtr, PA15, 1;
+
     TIM3, PinC1, PA6, 2;
     TIM3, PinC2, PA7, 2;
     TIM3, PinC3, PB0, 2;
@@ -477,6 +842,8 @@ channel_impl!(
     TIM3, PinC3, PC8, 2;
     TIM3, PinC4, PC9, 2;
 
+    TIM3, PinEtr, PD2, 2;
+
     TIM4, PinC1, PB6, 2;
     TIM4, PinC2, PB7, 2;
     TIM4, PinC3, PB8, 2;
@@ -486,6 +853,8 @@ channel_impl!(
     TIM4, PinC2, PD13, 2;
     TIM4, PinC3, PD14, 2;
     TIM4, PinC4, PD15, 2;
+
+    TIM4, PinEtr, PE0, 2;
 );
 
 #[cfg(feature = "tim8")]
@@ -494,6 +863,17 @@ channel_impl!(
     TIM8, PinC2, PC7, 3;
     TIM8, PinC3, PC8, 3;
     TIM8, PinC4, PC9, 3;
+
+    TIM8, PinC1N, PA5, 3;
+    TIM8, PinC1N, PA7, 3;
+    TIM8, PinC2N, PB0, 3;
+    TIM8, PinC2N, PB14, 3;
+    TIM8, PinC3N, PB1, 3;
+    TIM8, PinC3N, PB15, 3;
+
+    TIM8, PinBkin, PA6, 3;
+
+    TIM8, PinEtr, PA0, 3;
 );
 
 #[cfg(any(
@@ -518,6 +898,14 @@ channel_impl!(
     TIM8, PinC2, PI6, 3;
     TIM8, PinC3, PI7, 3;
     TIM8, PinC4, PI2, 3;
+
+    TIM8, PinC1N, PH13, 3;
+    TIM8, PinC2N, PH14, 3;
+    TIM8, PinC3N, PH15, 3;
+
+    TIM8, PinBkin, PI4, 3;
+
+    TIM8, PinEtr, PI3, 3;
 );
 
 #[cfg(any(feature = "stm32f412", feature = "stm32f413", feature = "stm32f423"))]
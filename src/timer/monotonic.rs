@@ -0,0 +1,363 @@
+//! Monotonic timers for RTIC style scheduling
+//!
+//! The counter runs freely at a configurable tick rate and capture/compare channel 1 is used to
+//! raise an interrupt at the next scheduled instant. `MonoTimer32` uses the full 32-bit counter
+//! of TIM2/TIM5, `MonoTimer64` extends a 16-bit counter to 64 bits by counting overflows in the
+//! update interrupt. Both the compare and the update interrupt share the timer's global
+//! interrupt vector.
+//!
+//! With the `rtic-monotonic` feature, `into_rtic` wraps a timer in [`Mono`], which implements
+//! `rtic_monotonic::Monotonic` with `fugit` instants at a tick rate fixed at compile time:
+//!
+//! ```ignore
+//! type MyMono = Mono<MonoTimer32<pac::TIM2>, 1_000_000>;
+//!
+//! let mono: MyMono = Timer::new(dp.TIM2, &clocks)
+//!     .monotonic(1.mhz())
+//!     .unwrap()
+//!     .into_rtic()
+//!     .unwrap();
+//! ```
+
+use super::{prescaler, Error, Flags, Timer};
+use crate::time::Hertz;
+
+/// Capture/compare 1 interrupt flag
+const CC1IF: u32 = 1 << 1;
+/// Update interrupt flag
+#[cfg(not(feature = "stm32f410"))]
+const UIF: u32 = 1 << 0;
+
+/// Monotonic timer using a 32-bit general purpose timer
+pub struct MonoTimer32<TIM> {
+    tim: TIM,
+    freq: Hertz,
+}
+
+/// Monotonic timer using a 16-bit timer, extended to 64 bits in software
+///
+/// [`on_interrupt`](MonoTimer64::on_interrupt) must be called from the timer interrupt for the
+/// overflows to be counted.
+#[cfg(not(feature = "stm32f410"))]
+pub struct MonoTimer64<TIM> {
+    tim: TIM,
+    freq: Hertz,
+    ovf: u64,
+}
+
+/// Monotonic timer counting at `FREQ` Hz, implementing `rtic_monotonic::Monotonic`
+#[cfg(feature = "rtic-monotonic")]
+pub struct Mono<MONO, const FREQ: u32>(MONO);
+
+#[cfg(feature = "rtic-monotonic")]
+impl<MONO, const FREQ: u32> Mono<MONO, FREQ> {
+    /// Releases the monotonic timer
+    pub fn release(self) -> MONO {
+        self.0
+    }
+}
+
+macro_rules! start {
+    ($tim:expr, $clk:expr, $tick_rate:expr, $max:expr) => {{
+        let psc = prescaler($clk, $tick_rate.into())?;
+
+        // pause
+        $tim.cr1.modify(|_, w| w.cen().clear_bit());
+        $tim.psc.write(|w| w.psc().bits(psc));
+        $tim.arr.write(|w| unsafe { w.bits($max) });
+
+        // Trigger update event to load the registers
+        $tim.cr1.modify(|_, w| w.urs().set_bit());
+        $tim.egr.write(|w| w.ug().set_bit());
+        $tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+        // start counter
+        $tim.cr1.modify(|_, w| w.cen().set_bit());
+
+        Hertz($clk.0 / (u32::from(psc) + 1))
+    }};
+}
+
+macro_rules! mono32 {
+    ($($TIM:ty,)+) => {
+        $(
+            impl Timer<$TIM> {
+                /// Starts the timer as a free running 32-bit monotonic at `tick_rate`
+                ///
+                /// The prescaler is computed from the timer clock, the effective rate is
+                /// returned by [`MonoTimer32::frequency`]. Returns `Error::TickRate` if the tick
+                /// rate is 0, above the timer clock or too low for the prescaler.
+                pub fn monotonic<T>(self, tick_rate: T) -> Result<MonoTimer32<$TIM>, Error>
+                where
+                    T: Into<Hertz>,
+                {
+                    let Self { tim, clk } = self;
+                    let freq = start!(tim, clk, tick_rate, 0xffff_ffff);
+                    Ok(MonoTimer32 { tim, freq })
+                }
+            }
+
+            impl MonoTimer32<$TIM> {
+                /// Returns the tick rate of the counter
+                pub fn frequency(&self) -> Hertz {
+                    self.freq
+                }
+
+                /// Returns the current counter value
+                pub fn now(&mut self) -> u32 {
+                    self.tim.cnt.read().bits()
+                }
+
+                /// Schedules the compare interrupt at `instant`
+                pub fn set_compare(&mut self, instant: u32) {
+                    self.tim.ccr1.write(|w| unsafe { w.bits(instant) });
+                }
+
+                /// Clears the compare interrupt flag
+                pub fn clear_compare_flag(&mut self) {
+                    // NOTE(unsafe) writing 1 to the other flags has no effect
+                    self.tim
+                        .sr
+                        .write(|w| unsafe { w.bits(<$TIM as Flags>::FLAGS & !CC1IF) });
+                }
+
+                /// Resets the counter to zero and enables the compare interrupt
+                pub fn reset(&mut self) {
+                    self.tim.cnt.reset();
+                    self.clear_compare_flag();
+                    self.tim.dier.modify(|_, w| w.cc1ie().set_bit());
+                }
+
+                /// Nothing to do for the 32-bit timer, provided for symmetry with `MonoTimer64`
+                pub fn on_interrupt(&mut self) {}
+
+                /// Stops the timer and releases the TIM peripheral
+                pub fn release(self) -> $TIM {
+                    self.tim.dier.modify(|_, w| w.cc1ie().clear_bit());
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    self.tim
+                }
+
+                /// Wraps the timer for RTIC, returns `Error::TickRate` if the timer does not
+                /// count at exactly `FREQ` Hz
+                #[cfg(feature = "rtic-monotonic")]
+                pub fn into_rtic<const FREQ: u32>(self) -> Result<Mono<Self, FREQ>, Error> {
+                    if self.freq.0 == FREQ {
+                        Ok(Mono(self))
+                    } else {
+                        Err(Error::TickRate)
+                    }
+                }
+            }
+
+            #[cfg(feature = "rtic-monotonic")]
+            impl<const FREQ: u32> rtic_monotonic::Monotonic for Mono<MonoTimer32<$TIM>, FREQ> {
+                type Instant = fugit::TimerInstantU32<FREQ>;
+                type Duration = fugit::TimerDurationU32<FREQ>;
+
+                fn now(&mut self) -> Self::Instant {
+                    Self::Instant::from_ticks(self.0.now())
+                }
+
+                fn set_compare(&mut self, instant: Self::Instant) {
+                    self.0.set_compare(instant.ticks());
+                }
+
+                fn clear_compare_flag(&mut self) {
+                    self.0.clear_compare_flag();
+                }
+
+                fn zero() -> Self::Instant {
+                    Self::Instant::from_ticks(0)
+                }
+
+                unsafe fn reset(&mut self) {
+                    self.0.reset();
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(not(feature = "stm32f410"))]
+macro_rules! mono64 {
+    ($($TIM:ty,)+) => {
+        $(
+            impl Timer<$TIM> {
+                /// Starts the timer as a free running monotonic at `tick_rate`, extended to 64
+                /// bits by counting overflows
+                ///
+                /// The prescaler is computed from the timer clock, the effective rate is
+                /// returned by [`MonoTimer64::frequency`]. Returns `Error::TickRate` if the tick
+                /// rate is 0, above the timer clock or too low for the prescaler.
+                pub fn monotonic64<T>(self, tick_rate: T) -> Result<MonoTimer64<$TIM>, Error>
+                where
+                    T: Into<Hertz>,
+                {
+                    let Self { tim, clk } = self;
+                    let freq = start!(tim, clk, tick_rate, 0xffff);
+                    Ok(MonoTimer64 { tim, freq, ovf: 0 })
+                }
+            }
+
+            impl MonoTimer64<$TIM> {
+                /// Returns the tick rate of the counter
+                pub fn frequency(&self) -> Hertz {
+                    self.freq
+                }
+
+                /// Returns the current extended counter value
+                pub fn now(&mut self) -> u64 {
+                    loop {
+                        // An overflow that is not yet counted shows up as a pending update
+                        // flag, retry if it appeared while reading the counter
+                        let before = self.tim.sr.read().uif().bit_is_set();
+                        let cnt = self.tim.cnt.read().bits() & 0xffff;
+                        let after = self.tim.sr.read().uif().bit_is_set();
+                        if before == after {
+                            let pending = if after { 0x1_0000 } else { 0 };
+                            return self.ovf + pending + u64::from(cnt);
+                        }
+                    }
+                }
+
+                /// Schedules the compare interrupt at `instant`
+                ///
+                /// Instants beyond the current counter period are reached through the update
+                /// interrupt, after which the scheduler sets the compare again.
+                pub fn set_compare(&mut self, instant: u64) {
+                    let ccr = match instant.checked_sub(self.ovf) {
+                        Some(ticks) if ticks < 0x1_0000 => ticks as u32,
+                        _ => 0xffff,
+                    };
+                    self.tim.ccr1.write(|w| unsafe { w.bits(ccr) });
+                }
+
+                /// Clears the compare interrupt flag
+                pub fn clear_compare_flag(&mut self) {
+                    // NOTE(unsafe) writing 1 to the other flags has no effect
+                    self.tim
+                        .sr
+                        .write(|w| unsafe { w.bits(<$TIM as Flags>::FLAGS & !CC1IF) });
+                }
+
+                /// Resets the counter to zero and enables the compare and update interrupts
+                pub fn reset(&mut self) {
+                    self.tim.cnt.reset();
+                    self.ovf = 0;
+                    self.tim
+                        .sr
+                        .write(|w| unsafe { w.bits(<$TIM as Flags>::FLAGS & !(CC1IF | UIF)) });
+                    self.tim
+                        .dier
+                        .modify(|_, w| w.cc1ie().set_bit().uie().set_bit());
+                }
+
+                /// Counts a counter overflow, call this from the timer interrupt
+                pub fn on_interrupt(&mut self) {
+                    if self.tim.sr.read().uif().bit_is_set() {
+                        self.tim
+                            .sr
+                            .write(|w| unsafe { w.bits(<$TIM as Flags>::FLAGS & !UIF) });
+                        self.ovf += 0x1_0000;
+                    }
+                }
+
+                /// Stops the timer and releases the TIM peripheral
+                pub fn release(self) -> $TIM {
+                    self.tim
+                        .dier
+                        .modify(|_, w| w.cc1ie().clear_bit().uie().clear_bit());
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    self.tim
+                }
+
+                /// Wraps the timer for RTIC, returns `Error::TickRate` if the timer does not
+                /// count at exactly `FREQ` Hz
+                #[cfg(feature = "rtic-monotonic")]
+                pub fn into_rtic<const FREQ: u32>(self) -> Result<Mono<Self, FREQ>, Error> {
+                    if self.freq.0 == FREQ {
+                        Ok(Mono(self))
+                    } else {
+                        Err(Error::TickRate)
+                    }
+                }
+            }
+
+            #[cfg(feature = "rtic-monotonic")]
+            impl<const FREQ: u32> rtic_monotonic::Monotonic for Mono<MonoTimer64<$TIM>, FREQ> {
+                // The update interrupt must keep counting overflows
+                const DISABLE_INTERRUPT_ON_EMPTY_QUEUE: bool = false;
+
+                type Instant = fugit::TimerInstantU64<FREQ>;
+                type Duration = fugit::TimerDurationU64<FREQ>;
+
+                fn now(&mut self) -> Self::Instant {
+                    Self::Instant::from_ticks(self.0.now())
+                }
+
+                fn set_compare(&mut self, instant: Self::Instant) {
+                    self.0.set_compare(instant.ticks());
+                }
+
+                fn clear_compare_flag(&mut self) {
+                    self.0.clear_compare_flag();
+                }
+
+                fn zero() -> Self::Instant {
+                    Self::Instant::from_ticks(0)
+                }
+
+                unsafe fn reset(&mut self) {
+                    self.0.reset();
+                }
+
+                fn on_interrupt(&mut self) {
+                    self.0.on_interrupt();
+                }
+            }
+        )+
+    };
+}
+
+mono32!(crate::pac::TIM5,);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+mono32!(crate::pac::TIM2,);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+mono64!(crate::pac::TIM3, crate::pac::TIM4,);
@@ -0,0 +1,195 @@
+//! Software alarms multiplexed on one timer
+//!
+//! Any number of alarms, one-shot or periodic, share a single 32-bit timer: the counter runs
+//! freely and is extended to 64 bits by counting its overflows in the update interrupt, and the
+//! capture/compare channel 1 interrupt is set at the earliest alarm. Alarms fire by calling their
+//! callback from the timer interrupt and by setting a flag that can be polled.
+//!
+//! The scheduling is done by [`Scheduler`], which only deals with tick counts and does not touch
+//! the hardware, so it can also be driven from another time source and is tested on the host.
+
+use super::{prescaler, Flags, Timer};
+use crate::time::Hertz;
+
+mod scheduler;
+pub use self::scheduler::{AlarmId, Error, Scheduler};
+
+/// Capture/compare 1 interrupt flag
+const CC1IF: u32 = 1 << 1;
+/// Update interrupt flag
+const UIF: u32 = 1 << 0;
+
+/// Up to `N` software alarms on a 32-bit timer
+///
+/// [`on_interrupt`](TimerWheel::on_interrupt) must be called from the timer interrupt.
+pub struct TimerWheel<TIM, const N: usize> {
+    tim: TIM,
+    freq: Hertz,
+    overflows: u64,
+    scheduler: Scheduler<N>,
+}
+
+macro_rules! wheel {
+    ($($TIM:ty,)+) => {
+        $(
+            impl Timer<$TIM> {
+                /// Starts the timer counting at `tick_rate` to drive up to `N` software alarms
+                ///
+                /// The prescaler is computed from the timer clock, the effective rate is
+                /// returned by [`TimerWheel::frequency`]. Returns `timer::Error::TickRate` if the tick
+                /// rate is 0, above the timer clock or too low for the prescaler.
+                pub fn wheel<T, const N: usize>(
+                    self,
+                    tick_rate: T,
+                ) -> Result<TimerWheel<$TIM, N>, super::Error>
+                where
+                    T: Into<Hertz>,
+                {
+                    let Self { tim, clk } = self;
+                    let psc = prescaler(clk, tick_rate.into())?;
+
+                    tim.psc.write(|w| w.psc().bits(psc));
+                    tim.arr.write(|w| unsafe { w.bits(0xffff_ffff) });
+
+                    // Trigger update event to load the registers
+                    tim.cr1.modify(|_, w| w.urs().set_bit());
+                    tim.egr.write(|w| w.ug().set_bit());
+                    tim.cr1.modify(|_, w| w.urs().clear_bit());
+
+                    // NOTE(unsafe) writing 1 to the other flags has no effect
+                    tim.sr
+                        .write(|w| unsafe { w.bits(<$TIM as Flags>::FLAGS & !(CC1IF | UIF)) });
+                    tim.dier.modify(|_, w| w.cc1ie().set_bit().uie().set_bit());
+                    tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                    Ok(TimerWheel {
+                        tim,
+                        freq: Hertz(clk.0 / (u32::from(psc) + 1)),
+                        overflows: 0,
+                        scheduler: Scheduler::new(),
+                    })
+                }
+            }
+
+            impl<const N: usize> TimerWheel<$TIM, N> {
+                /// Returns the tick rate of the counter
+                pub fn frequency(&self) -> Hertz {
+                    self.freq
+                }
+
+                /// Returns the ticks elapsed since the timer was started
+                pub fn now(&self) -> u64 {
+                    loop {
+                        // An overflow that is not yet counted shows up as a pending update
+                        // flag, retry if it appeared while reading the counter
+                        let before = self.tim.sr.read().uif().bit_is_set();
+                        let cnt = self.tim.cnt.read().bits();
+                        let after = self.tim.sr.read().uif().bit_is_set();
+                        if before == after {
+                            let pending = if after { 1 << 32 } else { 0 };
+                            return self.overflows + pending + u64::from(cnt);
+                        }
+                    }
+                }
+
+                /// Schedules a one-shot alarm in `delay` ticks
+                pub fn schedule(&mut self, delay: u64, callback: Option<fn()>) -> Result<AlarmId, Error> {
+                    let id = self.scheduler.schedule(self.now(), delay, 0, callback)?;
+                    self.arm();
+                    Ok(id)
+                }
+
+                /// Schedules a periodic alarm every `period` ticks
+                pub fn schedule_periodic(
+                    &mut self,
+                    period: u64,
+                    callback: Option<fn()>,
+                ) -> Result<AlarmId, Error> {
+                    let id = self.scheduler.schedule(self.now(), period, period.max(1), callback)?;
+                    self.arm();
+                    Ok(id)
+                }
+
+                /// Arms the alarm again in `delay` ticks
+                pub fn restart(&mut self, id: AlarmId, delay: u64) -> Result<(), Error> {
+                    self.scheduler.restart(id, self.now(), delay)?;
+                    self.arm();
+                    Ok(())
+                }
+
+                /// Cancels the alarm and frees its slot
+                pub fn cancel(&mut self, id: AlarmId) -> Result<(), Error> {
+                    self.scheduler.cancel(id)
+                }
+
+                /// Returns `true` once if the alarm has fired since the last call
+                pub fn is_fired(&mut self, id: AlarmId) -> Result<bool, Error> {
+                    self.scheduler.is_fired(id)
+                }
+
+                /// Counts the overflows and fires the due alarms, call this from the timer
+                /// interrupt
+                pub fn on_interrupt(&mut self) {
+                    let clear = self.tim.sr.read().bits() & (CC1IF | UIF);
+                    if clear != 0 {
+                        // NOTE(unsafe) writing 1 to the other flags has no effect
+                        self.tim
+                            .sr
+                            .write(|w| unsafe { w.bits(<$TIM as Flags>::FLAGS & !clear) });
+                    }
+                    if clear & UIF != 0 {
+                        self.overflows += 1 << 32;
+                    }
+                    self.scheduler.process(self.now());
+                    self.arm();
+                }
+
+                /// Stops the timer and releases the TIM peripheral
+                pub fn release(self) -> $TIM {
+                    self.tim.dier.modify(|_, w| w.cc1ie().clear_bit().uie().clear_bit());
+                    self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                    self.tim
+                }
+
+                // Sets the compare interrupt at the next deadline, if it is in the current counter
+                // period, the update interrupt is reached first otherwise
+                fn arm(&mut self) {
+                    let deadline = match self.scheduler.next_deadline() {
+                        Some(deadline) => deadline,
+                        None => return,
+                    };
+                    let now = self.now();
+                    if deadline >> 32 == now >> 32 {
+                        self.tim.ccr1.write(|w| unsafe { w.bits(deadline as u32) });
+                    }
+                    // Fire by software if the deadline is due or was passed while setting it
+                    if deadline <= self.now() {
+                        self.tim.egr.write(|w| w.cc1g().set_bit());
+                    }
+                }
+            }
+        )+
+    };
+}
+
+wheel!(crate::pac::TIM5,);
+
+#[cfg(any(
+    feature = "stm32f401",
+    feature = "stm32f405",
+    feature = "stm32f407",
+    feature = "stm32f411",
+    feature = "stm32f412",
+    feature = "stm32f413",
+    feature = "stm32f415",
+    feature = "stm32f417",
+    feature = "stm32f423",
+    feature = "stm32f427",
+    feature = "stm32f429",
+    feature = "stm32f437",
+    feature = "stm32f439",
+    feature = "stm32f446",
+    feature = "stm32f469",
+    feature = "stm32f479"
+))]
+wheel!(crate::pac::TIM2,);
@@ -0,0 +1,266 @@
+//! Alarm scheduling on a monotonic tick count
+//!
+//! [`Scheduler`] keeps up to `N` alarms, one-shot or periodic, and fires the due ones when it is
+//! given the current tick count. It does not touch the hardware, [`TimerWheel`](super::TimerWheel)
+//! drives it from a timer but any other time source can be used.
+
+/// Alarm error
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Error {
+    /// All the alarm slots are in use
+    Full,
+    /// The id does not refer to a scheduled alarm, it was cancelled or its slot was reused
+    InvalidId,
+}
+
+/// Handle of a scheduled alarm
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct AlarmId {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Copy, Clone)]
+struct Alarm {
+    deadline: u64,
+    period: u64,
+    callback: Option<fn()>,
+    armed: bool,
+    fired: bool,
+}
+
+#[derive(Copy, Clone)]
+struct Slot {
+    // Incremented when the alarm is cancelled, so that its id can't reach a later alarm
+    generation: u32,
+    alarm: Option<Alarm>,
+}
+
+/// Scheduling of up to `N` alarms on a monotonic tick count
+pub struct Scheduler<const N: usize> {
+    slots: [Slot; N],
+}
+
+impl<const N: usize> Scheduler<N> {
+    /// Creates a scheduler without alarms
+    pub const fn new() -> Self {
+        Self {
+            slots: [Slot {
+                generation: 0,
+                alarm: None,
+            }; N],
+        }
+    }
+
+    /// Schedules an alarm `delay` ticks after `now`, then every `period` ticks if `period` is
+    /// not 0. `callback` is called when the alarm fires.
+    ///
+    /// The alarm stays allocated until it is cancelled, a one-shot alarm can be restarted.
+    pub fn schedule(
+        &mut self,
+        now: u64,
+        delay: u64,
+        period: u64,
+        callback: Option<fn()>,
+    ) -> Result<AlarmId, Error> {
+        let index = self
+            .slots
+            .iter()
+            .position(|slot| slot.alarm.is_none())
+            .ok_or(Error::Full)?;
+        let slot = &mut self.slots[index];
+        slot.alarm = Some(Alarm {
+            deadline: now.saturating_add(delay),
+            period,
+            callback,
+            armed: true,
+            fired: false,
+        });
+        Ok(AlarmId {
+            index,
+            generation: slot.generation,
+        })
+    }
+
+    /// Arms the alarm again `delay` ticks after `now`, clearing its fired flag
+    pub fn restart(&mut self, id: AlarmId, now: u64, delay: u64) -> Result<(), Error> {
+        let alarm = self.alarm(id)?;
+        alarm.deadline = now.saturating_add(delay);
+        alarm.armed = true;
+        alarm.fired = false;
+        Ok(())
+    }
+
+    /// Cancels the alarm and frees its slot
+    pub fn cancel(&mut self, id: AlarmId) -> Result<(), Error> {
+        self.alarm(id)?;
+        let slot = &mut self.slots[id.index];
+        slot.alarm = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Returns `true` once if the alarm has fired since the last call
+    pub fn is_fired(&mut self, id: AlarmId) -> Result<bool, Error> {
+        let alarm = self.alarm(id)?;
+        let fired = alarm.fired;
+        alarm.fired = false;
+        Ok(fired)
+    }
+
+    /// Returns the earliest deadline of the armed alarms
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.alarm.as_ref())
+            .filter(|alarm| alarm.armed)
+            .map(|alarm| alarm.deadline)
+            .min()
+    }
+
+    /// Fires the alarms whose deadline is not after `now`, returns how many fired
+    ///
+    /// A periodic alarm fires once even if several periods have elapsed, and is set to its next
+    /// deadline after `now`.
+    pub fn process(&mut self, now: u64) -> usize {
+        let mut fired = 0;
+        for alarm in self.slots.iter_mut().filter_map(|slot| slot.alarm.as_mut()) {
+            if !alarm.armed || alarm.deadline > now {
+                continue;
+            }
+            match (now - alarm.deadline).checked_div(alarm.period) {
+                Some(missed) => {
+                    alarm.deadline = alarm
+                        .deadline
+                        .saturating_add((missed + 1).saturating_mul(alarm.period))
+                }
+                None => alarm.armed = false,
+            }
+            alarm.fired = true;
+            fired += 1;
+            if let Some(callback) = alarm.callback {
+                callback();
+            }
+        }
+        fired
+    }
+
+    fn alarm(&mut self, id: AlarmId) -> Result<&mut Alarm, Error> {
+        self.slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.alarm.as_mut())
+            .ok_or(Error::InvalidId)
+    }
+}
+
+impl<const N: usize> Default for Scheduler<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_shot_fires_once() {
+        let mut scheduler = Scheduler::<2>::new();
+        let id = scheduler.schedule(100, 50, 0, None).unwrap();
+
+        assert_eq!(scheduler.process(149), 0);
+        assert_eq!(scheduler.is_fired(id), Ok(false));
+        assert_eq!(scheduler.process(150), 1);
+        assert_eq!(scheduler.is_fired(id), Ok(true));
+        assert_eq!(scheduler.is_fired(id), Ok(false));
+        assert_eq!(scheduler.process(1_000), 0);
+        assert_eq!(scheduler.next_deadline(), None);
+    }
+
+    #[test]
+    fn restart_rearms() {
+        let mut scheduler = Scheduler::<1>::new();
+        let id = scheduler.schedule(0, 10, 0, None).unwrap();
+        assert_eq!(scheduler.process(10), 1);
+
+        scheduler.restart(id, 20, 5).unwrap();
+        assert_eq!(scheduler.is_fired(id), Ok(false));
+        assert_eq!(scheduler.next_deadline(), Some(25));
+        assert_eq!(scheduler.process(24), 0);
+        assert_eq!(scheduler.process(25), 1);
+        assert_eq!(scheduler.is_fired(id), Ok(true));
+    }
+
+    #[test]
+    fn cancel_frees_the_slot() {
+        let mut scheduler = Scheduler::<1>::new();
+        let id = scheduler.schedule(0, 10, 0, None).unwrap();
+        assert_eq!(scheduler.schedule(0, 10, 0, None), Err(Error::Full));
+
+        scheduler.cancel(id).unwrap();
+        assert_eq!(scheduler.next_deadline(), None);
+        assert_eq!(scheduler.process(10), 0);
+        assert_eq!(scheduler.cancel(id), Err(Error::InvalidId));
+        assert!(scheduler.schedule(0, 10, 0, None).is_ok());
+    }
+
+    #[test]
+    fn stale_id_does_not_reach_reused_slot() {
+        let mut scheduler = Scheduler::<1>::new();
+        let stale = scheduler.schedule(0, 10, 0, None).unwrap();
+        scheduler.cancel(stale).unwrap();
+        let id = scheduler.schedule(0, 20, 0, None).unwrap();
+
+        assert_ne!(stale, id);
+        assert_eq!(scheduler.restart(stale, 0, 5), Err(Error::InvalidId));
+        assert_eq!(scheduler.is_fired(stale), Err(Error::InvalidId));
+        assert_eq!(scheduler.cancel(stale), Err(Error::InvalidId));
+        assert_eq!(scheduler.next_deadline(), Some(20));
+    }
+
+    #[test]
+    fn full() {
+        let mut scheduler = Scheduler::<2>::new();
+        scheduler.schedule(0, 1, 0, None).unwrap();
+        scheduler.schedule(0, 2, 0, None).unwrap();
+        assert_eq!(scheduler.schedule(0, 3, 0, None), Err(Error::Full));
+
+        // Fired one-shot alarms keep their slot until cancelled
+        assert_eq!(scheduler.process(10), 2);
+        assert_eq!(scheduler.schedule(0, 3, 0, None), Err(Error::Full));
+    }
+
+    #[test]
+    fn periodic_catches_up_missed_periods() {
+        let mut scheduler = Scheduler::<1>::new();
+        let id = scheduler.schedule(0, 10, 10, None).unwrap();
+
+        assert_eq!(scheduler.process(10), 1);
+        assert_eq!(scheduler.next_deadline(), Some(20));
+        // Periods at 20, 30 and 40 were missed, it fires once and moves past `now`
+        assert_eq!(scheduler.process(45), 1);
+        assert_eq!(scheduler.is_fired(id), Ok(true));
+        assert_eq!(scheduler.next_deadline(), Some(50));
+        assert_eq!(scheduler.process(49), 0);
+        assert_eq!(scheduler.process(50), 1);
+        assert_eq!(scheduler.next_deadline(), Some(60));
+    }
+
+    #[test]
+    fn next_deadline_is_the_earliest_armed() {
+        let mut scheduler = Scheduler::<3>::new();
+        assert_eq!(scheduler.next_deadline(), None);
+
+        let late = scheduler.schedule(0, 300, 0, None).unwrap();
+        let early = scheduler.schedule(0, 100, 0, None).unwrap();
+        scheduler.schedule(0, 200, 0, None).unwrap();
+        assert_eq!(scheduler.next_deadline(), Some(100));
+
+        assert_eq!(scheduler.process(100), 1);
+        assert_eq!(scheduler.next_deadline(), Some(200));
+        scheduler.cancel(early).unwrap();
+        scheduler.restart(late, 100, 50).unwrap();
+        assert_eq!(scheduler.next_deadline(), Some(150));
+    }
+}
@@ -3,7 +3,7 @@
 use crate::{
     hal::watchdog::{Watchdog, WatchdogEnable},
     pac::{DBGMCU, IWDG},
-    time::MilliSeconds,
+    time::{MicroSeconds, MilliSeconds},
 };
 
 /// Wraps the Independent Watchdog (IWDG) peripheral
@@ -88,10 +88,12 @@ impl IndependentWatchdog {
 }
 
 impl WatchdogEnable for IndependentWatchdog {
-    type Time = MilliSeconds;
+    type Time = MicroSeconds;
 
     fn start<T: Into<Self::Time>>(&mut self, period: T) {
-        self.setup(period.into().0);
+        // The timeout has a millisecond resolution, round up
+        let us = period.into().0;
+        self.setup(us / 1000 + (us % 1000 != 0) as u32);
 
         self.iwdg.kr.write(|w| unsafe { w.key().bits(KR_START) });
     }